@@ -0,0 +1,106 @@
+#![feature(test)]
+
+extern crate test;
+extern crate spin_bitwise;
+
+use test::{Bencher, black_box};
+
+use std::sync::{Arc, Barrier};
+use spin_bitwise::{ReadWriteLock, RwLock, StdRwLock, SpinRwLock};
+use std::thread::{spawn, JoinHandle};
+
+#[allow(unused_variables)]
+fn thread_reader<L: ReadWriteLock<Target = i64>>(iter_count: u64, lock: Arc<L>) {
+    for idx in 0..black_box(iter_count) {
+        let locked = lock.read();
+    }
+}
+
+fn thread_writer<L: ReadWriteLock<Target = i64>>(thread_idx: u64, iter_count: u64, lock: Arc<L>) {
+    for _ in 0..black_box(iter_count) {
+        let mut locked = lock.write();
+        
+        if thread_idx % 2 == 0 {
+            *locked += 1
+        } else {
+            *locked -= 1
+        }
+    }
+}
+
+fn bench_multithreaded<L>(lock: L, iter_count: u64, readers: u64, writers: u64)
+    where L: ReadWriteLock<Target = i64> + Send + Sync + 'static
+{
+    let lock = Arc::new(lock);
+    let start_barrier = Arc::new(Barrier::new((readers + writers) as usize));
+    
+    let threads: Vec<JoinHandle<_>> = (0..readers + writers).map(
+        |idx| {
+            let lock = lock.clone();
+            let start_barrier = start_barrier.clone();
+            spawn(move || {
+                start_barrier.wait();
+                if idx < readers {
+                    thread_reader(iter_count, lock)
+                } else {
+                    thread_writer(idx - readers, iter_count, lock)
+                }
+            })
+        }
+    ).collect();
+    
+    #[allow(unused_must_use)]
+    for t in threads {
+        t.join();
+    }
+    
+    let mut counter = *lock.read();
+    
+    if writers % 2 == 1 {
+        counter -= iter_count as i64
+    }
+    
+    assert!(counter == 0, "At the end, we must have 0 items left in the counter (ACTUAL: {})", counter);
+}
+
+fn bench<L, F>(b: &mut Bencher, new: F, iter_count: u64, readers: u64, writers: u64)
+    where L: ReadWriteLock<Target = i64> + Send + Sync + 'static, F: Fn() -> L
+{
+    b.iter(|| {
+        bench_multithreaded(new(), iter_count, readers, writers)
+    });
+    
+    b.bytes = (iter_count * readers + iter_count * writers) * 1000 * 1000;
+}
+
+const ITER: u64 = 10000;
+
+#[bench]
+fn bench_bitwise_15_readers_1_writers(b: &mut Bencher) {
+    bench(b, || RwLock::new(0), ITER, 15, 1);
+}
+
+#[bench]
+fn bench_std_15_readers_1_writers(b: &mut Bencher) {
+    bench(b, || StdRwLock::new(0), ITER, 15, 1);
+}
+
+#[bench]
+fn bench_spin_15_readers_1_writers(b: &mut Bencher) {
+    bench(b, || SpinRwLock::new(0), ITER, 15, 1);
+}
+
+#[bench]
+fn bench_bitwise_4_readers_2_writers(b: &mut Bencher) {
+    bench(b, || RwLock::new(0), ITER, 4, 2);
+}
+
+#[bench]
+fn bench_std_4_readers_2_writers(b: &mut Bencher) {
+    bench(b, || StdRwLock::new(0), ITER, 4, 2);
+}
+
+#[bench]
+fn bench_spin_4_readers_2_writers(b: &mut Bencher) {
+    bench(b, || SpinRwLock::new(0), ITER, 4, 2);
+}
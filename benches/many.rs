@@ -6,11 +6,41 @@ extern crate spin_bitwise;
 
 use std::sync::{Arc, Barrier};
 use std::collections::HashMap;
-use spin_bitwise::RwLock;
+use spin_bitwise::{RwLock, LockSet};
 use test::{Bencher, black_box};
 use std::thread::{spawn, JoinHandle};
 
-fn thread_locks(thread_idx: u32, total_locks: u32, overlap: u32, locks: Arc<HashMap<u32, RwLock<u64>>>, iter_count: u32) {
+/// The way the threads obtain their locks
+#[derive(Clone, Copy)]
+enum Acquire {
+    Many,
+    Ordered,
+    Set,
+}
+
+fn checksum_and_write<'a, R, W>(read: R, write: W)
+    where R: Iterator<Item = u64>, W: Iterator<Item = &'a mut u64>
+{
+    let checksum = read.fold(0, |a, b| {
+        if a > b {
+            a
+        } else {
+            b
+        }
+    });
+    
+    for w in write {
+        *w = {
+            if *w > checksum {
+                *w + 1
+            } else {
+                checksum + 1
+            }
+        }
+    }
+}
+
+fn thread_locks(thread_idx: u32, total_locks: u32, overlap: u32, locks: Arc<HashMap<u32, RwLock<u64>>>, iter_count: u32, acquire: Acquire) {
     let write_key = thread_idx % total_locks;
     
     let in_range = |x| {
@@ -35,30 +65,38 @@ fn thread_locks(thread_idx: u32, total_locks: u32, overlap: u32, locks: Arc<Hash
         Some(y) => y
     }].to_vec();
     
-    for _ in 0..black_box(iter_count) {
-        let locking = RwLock::lock_many(thread_idx as usize, &read_locks, &write_locks);
+    if let Acquire::Set = acquire {
+        let mut lock_set = LockSet::new();
         
-        let checksum = locking.read.iter().map(|x| (*x).clone()).fold(0, |a, b| {
-            if a > b {
-                a
-            } else {
-                b
-            }
-        });
+        for r in read_locks.iter() {
+            lock_set.add_read(r);
+        }
         
-        for mut w in locking.write {
-            *w = {
-                if *w > checksum {
-                    *w + 1
-                } else {
-                    checksum + 1
-                }
-            }
+        for w in write_locks.iter() {
+            lock_set.add_write(w);
+        }
+        
+        for _ in 0..black_box(iter_count) {
+            let mut locking = lock_set.acquire(thread_idx as usize).unwrap();
+            let (read, write) = locking.split();
+            
+            checksum_and_write(read.iter().map(|x| **x), write.iter_mut().map(|x| &mut **x));
         }
+        
+        return;
+    }
+    
+    for _ in 0..black_box(iter_count) {
+        let mut locking = match acquire {
+            Acquire::Ordered => RwLock::lock_many_ordered(thread_idx as usize, &read_locks, &write_locks).unwrap(),
+            _ => RwLock::lock_many(thread_idx as usize, &read_locks, &write_locks).unwrap(),
+        };
+        
+        checksum_and_write(locking.read.iter().map(|x| **x), locking.write.iter_mut().map(|x| &mut **x));
     }
 }
 
-fn test(iter_count: u32, threads: u32) {
+fn test(iter_count: u32, threads: u32, acquire: Acquire) {
     let total_locks = threads;
     let overlap = threads;
     let lock_init_val: u64 = 0;
@@ -66,7 +104,7 @@ fn test(iter_count: u32, threads: u32) {
     let start_barrier = Arc::new(Barrier::new(total_locks as usize));
     
     for idx in 0..total_locks {
-        locks.insert(idx as u32, RwLock::new(lock_init_val));
+        locks.insert(idx, RwLock::new(lock_init_val));
     }
     
     let locks = Arc::new(locks);
@@ -78,11 +116,12 @@ fn test(iter_count: u32, threads: u32) {
             spawn(move || {
                 start_barrier.wait();
                 thread_locks(
-                    thread_idx as u32,
+                    thread_idx,
                     total_locks,
                     overlap,
                     locks,
-                    iter_count
+                    iter_count,
+                    acquire
                 )
             })
         }
@@ -93,18 +132,18 @@ fn test(iter_count: u32, threads: u32) {
         lock.join();
     }
     
-    let result = (0..total_locks).map(|x| (*(locks.get(&x).unwrap().read(0))).clone()).max().unwrap();
+    let result = (0..total_locks).map(|x| *locks.get(&x).unwrap().read(0)).max().unwrap();
     let total_iter_count = (total_locks * iter_count) as u64;
     
-    assert!(result == total_iter_count, format!("{} != {}", result, total_iter_count));
+    assert!(result == total_iter_count, "{} != {}", result, total_iter_count);
 }
 
 const ITER : u32 = 20000;
 
 
-fn bench_many(b: &mut Bencher, iter_count: u32, threads: u32) {
+fn bench_many(b: &mut Bencher, iter_count: u32, threads: u32, acquire: Acquire) {
     b.iter(move || {
-        test(iter_count, threads);
+        test(iter_count, threads, acquire);
     });
     
     b.bytes = threads as u64 * iter_count as u64 * 1000 * 1000;
@@ -112,20 +151,40 @@ fn bench_many(b: &mut Bencher, iter_count: u32, threads: u32) {
 
 #[bench]
 fn bench_many_10000_2_threads(b: &mut Bencher) {
-    bench_many(b, ITER, 2);
+    bench_many(b, ITER, 2, Acquire::Many);
 }
 
 #[bench]
 fn bench_many_10000_3_threads(b: &mut Bencher) {
-    bench_many(b, ITER, 3);
+    bench_many(b, ITER, 3, Acquire::Many);
 }
 
 #[bench]
 fn bench_many_1000_4_threads(b: &mut Bencher) {
-    bench_many(b, ITER, 4);
+    bench_many(b, ITER, 4, Acquire::Many);
 }
 
 #[bench]
 fn bench_many_1000_5_threads(b: &mut Bencher) {
-    bench_many(b, ITER, 5);
+    bench_many(b, ITER, 5, Acquire::Many);
+}
+
+#[bench]
+fn bench_many_ordered_1000_4_threads(b: &mut Bencher) {
+    bench_many(b, ITER, 4, Acquire::Ordered);
+}
+
+#[bench]
+fn bench_many_ordered_1000_5_threads(b: &mut Bencher) {
+    bench_many(b, ITER, 5, Acquire::Ordered);
+}
+
+#[bench]
+fn bench_many_set_1000_4_threads(b: &mut Bencher) {
+    bench_many(b, ITER, 4, Acquire::Set);
+}
+
+#[bench]
+fn bench_many_set_1000_5_threads(b: &mut Bencher) {
+    bench_many(b, ITER, 5, Acquire::Set);
 }
\ No newline at end of file
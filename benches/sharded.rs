@@ -0,0 +1,92 @@
+#![feature(test)]
+
+extern crate test;
+extern crate spin_bitwise;
+
+use test::{Bencher, black_box};
+
+use std::sync::{Arc, Barrier};
+use spin_bitwise::ShardedRwLock;
+use std::thread::{spawn, JoinHandle};
+
+#[allow(unused_variables)]
+fn thread_reader(thread_idx: u64, iter_count: u64, lock: Arc<ShardedRwLock<i64>>) {
+    for idx in 0..black_box(iter_count) {
+        let locked = lock.read(thread_idx as usize);
+    }
+}
+
+fn thread_writer(thread_idx: u64, iter_count: u64, lock: Arc<ShardedRwLock<i64>>) {
+    for _ in 0..black_box(iter_count) {
+        let mut locked = lock.write();
+        
+        if thread_idx % 2 == 0 {
+            *locked += 1
+        } else {
+            *locked -= 1
+        }
+    }
+}
+
+fn bench_multithreaded(iter_count: u64, readers: u64, writers: u64) {
+    let lock = Arc::new(ShardedRwLock::new(0));
+    let start_barrier = Arc::new(Barrier::new((readers + writers) as usize));
+    
+    let threads: Vec<JoinHandle<_>> = (0..readers + writers).map(
+        |idx| {
+            let lock = lock.clone();
+            let start_barrier = start_barrier.clone();
+            spawn(move || {
+                start_barrier.wait();
+                if idx < readers {
+                    thread_reader(idx, iter_count, lock)
+                } else {
+                    thread_writer(idx - readers, iter_count, lock)
+                }
+            })
+        }
+    ).collect();
+    
+    #[allow(unused_must_use)]
+    for t in threads {
+        t.join();
+    }
+    
+    let mut counter = *lock.read(0);
+    
+    if writers % 2 == 1 {
+        counter -= iter_count as i64
+    }
+    
+    assert!(counter == 0, "At the end, we must have 0 items left in the counter (ACTUAL: {})", counter);
+}
+
+fn bench(b: &mut Bencher, iter_count: u64, readers: u64, writers: u64) {
+    b.iter(|| {
+        bench_multithreaded(iter_count, readers, writers)
+    });
+    
+    b.bytes = (iter_count * readers + iter_count * writers) * 1000 * 1000;
+}
+
+const ITER: u64 = 10000;
+
+#[bench]
+fn bench_sharded_15_readers_0_writers(b: &mut Bencher) {
+    bench(b, ITER, 15, 0);
+}
+
+#[bench]
+fn bench_sharded_15_readers_1_writers(b: &mut Bencher) {
+    bench(b, ITER, 15, 1);
+}
+
+#[bench]
+fn bench_sharded_64_readers_0_writers(b: &mut Bencher) {
+    bench(b, ITER, 64, 0);
+}
+
+#[bench]
+fn bench_sharded_64_readers_2_writers(b: &mut Bencher) {
+    bench(b, ITER, 64, 2);
+}
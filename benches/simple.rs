@@ -5,7 +5,7 @@ extern crate spin_bitwise;
 
 use test::{Bencher, black_box};
 
-use spin_bitwise::RwLock;
+use spin_bitwise::{RwLock, SeqRwLock};
 
 #[bench]
 fn bench_reads(b: &mut Bencher) {
@@ -13,9 +13,23 @@ fn bench_reads(b: &mut Bencher) {
     let lock = RwLock::new(0);
     
     b.iter(|| {
-        for i in 0..black_box(total_iter) {
+        for _ in 0..black_box(total_iter) {
             let locked = lock.read(0);
-            *locked;
+            black_box(*locked);
+        }
+    });
+    
+    b.bytes = total_iter * 1000 * 1000;
+}
+
+#[bench]
+fn bench_seq_reads(b: &mut Bencher) {
+    let total_iter = 1000000;
+    let lock = SeqRwLock::new(0);
+    
+    b.iter(|| {
+        for _ in 0..black_box(total_iter) {
+            lock.read();
         }
     });
     
@@ -28,7 +42,22 @@ fn bench_writes(b: &mut Bencher) {
     let lock = RwLock::new(0);
     
     b.iter(|| {
-        for i in 0..black_box(total_iter) {
+        for _ in 0..black_box(total_iter) {
+            let mut locked = lock.write();
+            *locked += 1;
+        }
+    });
+    
+    b.bytes = total_iter * 1000 * 1000;
+}
+
+#[bench]
+fn bench_seq_writes(b: &mut Bencher) {
+    let total_iter = 1000000;
+    let lock = SeqRwLock::new(0);
+    
+    b.iter(|| {
+        for _ in 0..black_box(total_iter) {
             let mut locked = lock.write();
             *locked += 1;
         }
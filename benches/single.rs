@@ -82,7 +82,7 @@ fn bench_multithreaded(iter_count: u64, readers: u64, writers: u64) {
         counter -= iter_count as i64
     }
     
-    assert!(counter == 0, format!("At the end, we must have 0 items left in the counter (ACTUAL: {})", counter));
+    assert!(counter == 0, "At the end, we must have 0 items left in the counter (ACTUAL: {})", counter);
 }
 
 fn bench(b: &mut Bencher, iter_count: u64, readers: u64, writers: u64) {
@@ -2,7 +2,7 @@ extern crate spin_bitwise;
 
 
 use std::collections::HashMap;
-use spin_bitwise::{RwLock, random_reader_idx, ARCH};
+use spin_bitwise::{RwLock, ReaderIdxStrategy, ThreadIdHash, ARCH, lock_keys};
 
 fn main() {
     let total_locks = 6;
@@ -12,21 +12,21 @@ fn main() {
     let mut locks = HashMap::<u32, RwLock<u64>>::new();
     
     for idx in 0..total_locks {
-        locks.insert(idx as u32, RwLock::new(0));
+        locks.insert(idx, RwLock::new(0));
     }
     
-    // You may either generate a random reader id
-    let reader_id = random_reader_idx();
+    // You may either derive a reader id from the current thread
+    let _reader_id = ThreadIdHash.reader_idx();
     // Or you may supply a reader id from you own threading environment
     // But it must be less than `spin_bitwise::ARCH.reader_cnt`
     let reader_id = 0 % ARCH.reader_cnt;
     
-    // Make sure `read_locks` and `write_locks` do not overlap
-    let read_locks = (0..read_count).map(|x| locks.get(&x).unwrap()).collect();
-    let write_locks = (read_count..read_count + write_count).map(|x| locks.get(&x).unwrap()).collect();
+    // A lock in both `read_locks` and `write_locks` (or twice in one of them) is an error
+    let read_locks: Vec<_> = (0..read_count).map(|x| locks.get(&x).unwrap()).collect();
+    let write_locks: Vec<_> = (read_count..read_count + write_count).map(|x| locks.get(&x).unwrap()).collect();
     
     {
-        let locked = RwLock::lock_many(reader_id, &read_locks, &write_locks);
+        let locked = RwLock::lock_many(reader_id, &read_locks, &write_locks).unwrap();
         
         for mut x in locked.write {
             *x += 1;
@@ -38,6 +38,21 @@ fn main() {
         }
     }
     
+    {
+        // Or look the locks up by key, getting the guards back by key
+        let read_keys: Vec<u32> = (0..read_count).collect();
+        let write_keys: Vec<u32> = (read_count..read_count + write_count).collect();
+        
+        let mut locked = match lock_keys(reader_id, &locks, &read_keys, &write_keys) {
+            Ok(locked) => locked,
+            Err(err) => panic!("{}", err),
+        };
+        
+        for key in &write_keys {
+            **locked.write.get_mut(key).unwrap() += *locked.read[&0];
+        }
+    }
+    
     for x in 0..total_locks {
         let locked = locks.get(&x).unwrap().read(reader_id);
         println!("Key {} Value={}", x, *locked);
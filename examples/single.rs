@@ -1,12 +1,12 @@
 extern crate spin_bitwise;
 
-use spin_bitwise::{RwLock, random_reader_idx, ARCH};
+use spin_bitwise::{RwLock, ReaderIdxStrategy, ThreadIdHash, ARCH};
 
 fn main() {
     let lock = RwLock::new(0);
     
-    // You may either generate a random reader id
-    let reader_id = random_reader_idx();
+    // You may either derive a reader id from the current thread
+    let _reader_id = ThreadIdHash.reader_idx();
     // Or you may supply a reader id from you own threading environment
     // But it must be less than `spin_bitwise::ARCH.reader_cnt`
     let reader_id = 0 % ARCH.reader_cnt;
@@ -21,8 +21,15 @@ fn main() {
     }
     
     {
-        let mut locked = lock.read(reader_id);
+        let locked = lock.read(reader_id);
         
         println!("Value behind the lock is: {}", *locked);
     }
+    
+    // Or let the lock pick any free reader slot on its own
+    {
+        let locked = lock.read_any();
+        
+        println!("Value behind the lock is: {} (slot {})", *locked, locked.idx());
+    }
 }
\ No newline at end of file
@@ -4,12 +4,6 @@ pub struct Architecture
     pub reader_lock_mask: usize,
 }
 
-#[cfg(all(unix, target_pointer_width = "2"))]
-pub const ARCH: Architecture = Architecture {
-    reader_cnt: 1,
-    reader_lock_mask: 0b1,
-};
-
 #[cfg(all(unix, target_pointer_width = "32"))]
 pub const ARCH: Architecture = Architecture {
     reader_cnt: 31,
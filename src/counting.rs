@@ -0,0 +1,195 @@
+use core::cell::UnsafeCell;
+use core::ops::{Drop, Deref, DerefMut};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use util::cpu_relax;
+use helpers::*;
+use arch::ARCH;
+
+/// Width of a reader slot in `CountingRwLock`
+pub const SLOT_BITS: usize = 4;
+
+/// Layout of the lock word used by `CountingRwLock`
+pub struct CountingArchitecture
+{
+    /// Amount of reader slots in the word
+    pub slot_cnt: usize,
+    /// Amount of times a single slot may be held at once
+    pub slot_max: usize,
+}
+
+/// Slots are only ever incremented by a compare-and-swap that checks `slot_max` first, so
+/// a full slot never carries into the neighbouring one and every bit of a slot is used.
+pub const COUNTING_ARCH: CountingArchitecture = CountingArchitecture {
+    slot_cnt: ARCH.reader_cnt / SLOT_BITS,
+    slot_max: (1 << SLOT_BITS) - 1,
+};
+
+#[inline(always)]
+const fn slot_one(idx: usize) -> usize {
+    1 << (idx * SLOT_BITS)
+}
+
+#[inline(always)]
+const fn slot_count(state: usize, idx: usize) -> usize {
+    (state >> (idx * SLOT_BITS)) & ((1 << SLOT_BITS) - 1)
+}
+
+/// Provides single-writer multiple-reader lock where every reader slot is a small counter
+///
+/// # Description
+///
+/// A slot may be held up to `COUNTING_ARCH.slot_max` times concurrently, so a thread may
+/// re-enter `read(idx)` and several threads may share the same `idx`.
+///
+/// Obtaining a slot is a single compare-and-swap as long as the lock word does not change
+/// in between.
+pub struct CountingRwLock<T: ? Sized>
+{
+    lock: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+pub struct CountingReadLockGuard<'a, T: ? Sized + 'a>
+{
+    lock: &'a AtomicUsize,
+    data: &'a T,
+    pub idx: usize,
+}
+
+pub struct CountingWriteLockGuard<'a, T: ? Sized + 'a>
+{
+    lock: &'a AtomicUsize,
+    data: &'a mut T,
+}
+
+unsafe impl<T: ? Sized + Send + Sync> Sync for CountingRwLock<T> {}
+
+unsafe impl<T: ? Sized + Send> Send for CountingRwLock<T> {}
+
+enum SlotAttempt {
+    Owned,
+    Blocked,
+    Overflow,
+}
+
+impl<T> CountingRwLock<T>
+{
+    pub fn new(user_data: T) -> CountingRwLock<T>
+    {
+        CountingRwLock {
+            lock: AtomicUsize::new(0),
+            data: UnsafeCell::new(user_data),
+        }
+    }
+}
+
+impl<T: ? Sized> CountingRwLock<T>
+{
+    #[inline(always)]
+    fn attempt_reader_lock(&self, idx: usize) -> SlotAttempt {
+        let mut state = atomic_load(&self.lock);
+        
+        loop {
+            if state & bitmask_lock(ARCH.reader_cnt) != 0 {
+                return SlotAttempt::Blocked;
+            } else if slot_count(state, idx) >= COUNTING_ARCH.slot_max {
+                return SlotAttempt::Overflow;
+            }
+            
+            // Not an add: any amount of racing readers could push an add past `slot_max`
+            match self.lock.compare_exchange_weak(state, state + slot_one(idx), Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return SlotAttempt::Owned,
+                Err(current) => state = current,
+            }
+        }
+    }
+    
+    fn obtained_read(&self, idx: usize) -> CountingReadLockGuard<'_, T> {
+        CountingReadLockGuard {
+            idx,
+            lock: &self.lock,
+            data: unsafe { &*self.data.get() },
+        }
+    }
+    
+    /// Amount of times the slot `idx` is currently held
+    pub fn slot_count(&self, idx: usize) -> usize {
+        slot_count(atomic_load(&self.lock), idx % COUNTING_ARCH.slot_cnt)
+    }
+    
+    /// Obtain the lock in read mode
+    ///
+    /// # Arguments
+    /// * `idx` - reader slot, taken modulo `COUNTING_ARCH.slot_cnt`
+    ///
+    /// # Panics
+    ///
+    /// If the slot is already held `COUNTING_ARCH.slot_max` times.
+    pub fn read(&self, idx: usize) -> CountingReadLockGuard<'_, T>
+    {
+        let idx = idx % COUNTING_ARCH.slot_cnt;
+        
+        loop {
+            match self.attempt_reader_lock(idx) {
+                SlotAttempt::Owned => return self.obtained_read(idx),
+                SlotAttempt::Blocked => cpu_relax(),
+                SlotAttempt::Overflow => panic!(
+                    "Reader slot {} is already held {} times", idx, COUNTING_ARCH.slot_max
+                ),
+            }
+        }
+    }
+    
+    /// Makes a single attempt at obtaining the lock in read mode. Returns `None` if a writer
+    /// holds the lock or the slot is full.
+    pub fn try_read(&self, idx: usize) -> Option<CountingReadLockGuard<'_, T>>
+    {
+        let idx = idx % COUNTING_ARCH.slot_cnt;
+        
+        match self.attempt_reader_lock(idx) {
+            SlotAttempt::Owned => Some(self.obtained_read(idx)),
+            _ => None,
+        }
+    }
+    
+    /// Obtain the lock in write mode
+    pub fn write(&self) -> CountingWriteLockGuard<'_, T>
+    {
+        loop {
+            let (_, owned, block) = atomic_writer_lock(&self.lock);
+            
+            if owned && !block {
+                break;
+            } else {
+                cpu_relax();
+            }
+        }
+        
+        CountingWriteLockGuard {
+            lock: &self.lock,
+            data: unsafe { &mut *self.data.get() },
+        }
+    }
+}
+
+impl<'a, T: ? Sized> Drop for CountingReadLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.fetch_sub(slot_one(self.idx), Ordering::Release);
+    }
+}
+
+impl<'a, T: ? Sized> Drop for CountingWriteLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        atomic_writer_unlock(self.lock);
+    }
+}
+
+define_deref_for!(CountingReadLockGuard<'a, T>);
+define_deref_for!(CountingWriteLockGuard<'a, T>);
+define_deref_mut_for!(CountingWriteLockGuard<'a, T>);
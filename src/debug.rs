@@ -0,0 +1,95 @@
+//! Bookkeeping of the locks held by the current thread, enabled by the `debug_checks` feature.
+//!
+//! Without the feature all of the functions here are empty.
+//!
+//! The records live in a thread local list, so with the feature the guards of `RwLock` can
+//! not be sent to another thread (see `DebugMarker`). Leaked guards should be released by
+//! `force_unlock_*` on the thread that obtained them.
+
+use std::marker::PhantomData;
+use std::sync::atomic::AtomicUsize;
+
+#[cfg(feature = "debug_checks")]
+use std::cell::RefCell;
+
+/// The way a lock is held by the current thread
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Held {
+    /// Read mode through the given reader slot
+    Read(usize),
+    /// Read mode without a slot of its own, i.e. through the overflow counter
+    Shared,
+    Write,
+}
+
+/// Part of every guard recorded in the held list. With `debug_checks` it keeps the guard on
+/// the thread that obtained it: dropped anywhere else, its record would stay behind and turn
+/// later acquisitions on the obtaining thread into false self-deadlocks.
+#[cfg(feature = "debug_checks")]
+#[derive(Default)]
+pub struct DebugMarker(PhantomData<*const ()>);
+
+#[cfg(not(feature = "debug_checks"))]
+#[derive(Default)]
+pub struct DebugMarker(PhantomData<()>);
+
+/// Sharing a reference to the guard is fine, only dropping it elsewhere is not
+unsafe impl Sync for DebugMarker {}
+
+#[cfg(feature = "debug_checks")]
+thread_local!(static HELD: RefCell<Vec<(usize, Held)>> = const { RefCell::new(Vec::new()) });
+
+#[cfg(feature = "debug_checks")]
+#[inline(always)]
+fn key(lock: &AtomicUsize) -> usize {
+    lock as *const AtomicUsize as usize
+}
+
+/// Panics if obtaining `lock` the way `how` describes would wait for the current thread itself
+#[cfg(feature = "debug_checks")]
+pub fn debug_check(lock: &AtomicUsize, how: Held) {
+    HELD.with(|held| {
+        for &(other, other_how) in held.borrow().iter().filter(|&&(other, _)| other == key(lock)) {
+            match (how, other_how) {
+                (Held::Write, Held::Write) =>
+                    panic!("Lock {:#x} is written while the same thread already holds it in write mode", other),
+                (Held::Write, _) =>
+                    panic!("Lock {:#x} is written while the same thread holds it in read mode ({:?})", other, other_how),
+                (_, Held::Write) =>
+                    panic!("Lock {:#x} is read while the same thread holds it in write mode", other),
+                (Held::Read(idx), Held::Read(other_idx)) if idx == other_idx =>
+                    panic!("Lock {:#x} is read twice with the same idx {} by the same thread", other, idx),
+                _ => {}
+            }
+        }
+    })
+}
+
+#[cfg(feature = "debug_checks")]
+pub fn debug_acquired(lock: &AtomicUsize, how: Held) {
+    HELD.with(|held| held.borrow_mut().push((key(lock), how)))
+}
+
+#[cfg(feature = "debug_checks")]
+pub fn debug_released(lock: &AtomicUsize, how: Held) {
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        
+        // Leaked guards may still be released by another thread, the record is then left behind.
+        if let Some(pos) = held.iter().rposition(|&x| x == (key(lock), how)) {
+            held.remove(pos);
+        }
+    })
+}
+
+#[cfg(not(feature = "debug_checks"))]
+#[inline(always)]
+pub fn debug_check(_lock: &AtomicUsize, _how: Held) {}
+
+#[cfg(not(feature = "debug_checks"))]
+#[inline(always)]
+pub fn debug_acquired(_lock: &AtomicUsize, _how: Held) {}
+
+#[cfg(not(feature = "debug_checks"))]
+#[inline(always)]
+pub fn debug_released(_lock: &AtomicUsize, _how: Held) {}
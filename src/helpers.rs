@@ -1,5 +1,4 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
-//use std::thread::{current, ThreadId};
 
 use rand::random;
 use arch::ARCH;
@@ -34,12 +33,18 @@ pub fn atomic_lock(lock: Lock, idx: usize) -> usize {
 #[inline(always)]
 pub fn atomic_unlock(lock: Lock, idx: usize) -> usize {
     let mask = bitmask_lock(idx);
+    #[cfg(not(feature = "debug_checks"))]
     let ret = lock.fetch_xor(mask, ATOMICITY_RELEASE);
-    //    assert!(ret & mask == mask, "Can not allow to unlock a previously unlocked value");
+    // Clearing instead of flipping keeps the word intact if the check below fails
+    #[cfg(feature = "debug_checks")]
+    let ret = lock.fetch_and(!mask, ATOMICITY_RELEASE);
+    #[cfg(feature = "debug_checks")]
+    assert!(ret & mask == mask, "Can not allow to unlock a previously unlocked value (bit {}, lock word {:#x})", idx, ret);
     ret
 }
 
 
+/// Picks a random reader index on every call, see `ReaderIdxStrategy` for the alternatives
 pub fn random_reader_idx() -> usize {
     let r: usize = random();
     r % ARCH.reader_cnt
@@ -55,37 +60,62 @@ pub fn atomic_reader_lock(lock: Lock, idx: usize) -> (usize, bool, bool) {
     (prev_state, owned, block)
 }
 
+/// Claims the lowest free reader bit. Returns `None` if a writer holds the lock or every
+/// reader bit is taken.
+#[inline(always)]
+pub fn atomic_reader_lock_any(lock: Lock) -> Option<usize> {
+    let mut state = atomic_load(lock);
+    
+    loop {
+        let free = !state & bitmask_readers_lock();
+        
+        if state & bitmask_lock(ARCH.reader_cnt) != 0 || free == 0 {
+            return None;
+        }
+        
+        let idx = free.trailing_zeros() as usize;
+        
+        match lock.compare_exchange_weak(state, state | bitmask_lock(idx), Ordering::Acquire, ATOMICITY_LOAD) {
+            Ok(_) => return Some(idx),
+            Err(next_state) => state = next_state,
+        }
+    }
+}
+
 #[inline(always)]
 pub fn atomic_reader_unlock(lock: Lock, idx: usize) -> (usize, bool) {
     let prev_state = atomic_unlock(lock, idx);
     
-    //    assert!(prev_state & bitmask_lock(idx) == bitmask_lock(idx), "Must not happen");
-    
     (prev_state, false)
 }
 
 #[inline(always)]
-pub fn atomic_readers_free(lock: Lock) -> bool {
-    atomic_load(lock) & bitmask_readers_lock() == 0
+pub fn state_reader_slots(state: usize) -> usize {
+    state & bitmask_readers_lock()
+}
+
+#[inline(always)]
+pub fn state_writer_held(state: usize) -> bool {
+    state & bitmask_lock(ARCH.reader_cnt) != 0
 }
 
 #[inline(always)]
-pub fn atomic_reader_load(lock: Lock, idx: usize) -> bool {
-    atomic_load(lock) & bitmask_lock(idx) == 0
+pub fn atomic_readers_free(lock: Lock) -> bool {
+    state_reader_slots(atomic_load(lock)) == 0
 }
 
 #[inline(always)]
 pub fn atomic_writer_free(lock: Lock) -> bool {
-    atomic_load(lock) & bitmask_lock(ARCH.reader_cnt) == 0
+    !state_writer_held(atomic_load(lock))
 }
 
+/// Sequentially consistent, so that either the writer sees the overflow counter raised or the
+/// overflowing reader sees the writer bit (see `atomic_overflow_lock`).
 #[inline(always)]
 pub fn atomic_writer_lock(lock: Lock) -> (usize, bool, bool) {
-    let prev_state = lock.compare_and_swap(0, bitmask_lock(ARCH.reader_cnt), Ordering::Acquire);
-    if prev_state == 0 {
-        (0, true, false)
-    } else {
-        (prev_state, false, true)
+    match lock.compare_exchange(0, bitmask_lock(ARCH.reader_cnt), Ordering::SeqCst, Ordering::Relaxed) {
+        Ok(_) => (0, true, false),
+        Err(prev_state) => (prev_state, false, true),
     }
 }
 
@@ -94,4 +124,26 @@ pub fn atomic_writer_unlock(lock: Lock) -> (usize, bool) {
     atomic_reader_unlock(lock, ARCH.reader_cnt)
 }
 
+/// Counts a reader in the overflow counter, returns true if no writer holds the lock
+#[inline(always)]
+pub fn atomic_overflow_lock(lock: Lock, counter: Lock) -> bool {
+    counter.fetch_add(1, Ordering::SeqCst);
+    
+    if lock.load(Ordering::SeqCst) & bitmask_lock(ARCH.reader_cnt) == 0 {
+        true
+    } else {
+        atomic_overflow_unlock(counter);
+        false
+    }
+}
+
+#[inline(always)]
+pub fn atomic_overflow_unlock(counter: Lock) -> usize {
+    counter.fetch_sub(1, ATOMICITY_RELEASE)
+}
+
+#[inline(always)]
+pub fn atomic_overflow_free(counter: Lock) -> bool {
+    counter.load(Ordering::SeqCst) == 0
+}
 
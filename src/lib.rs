@@ -1,7 +1,5 @@
 #![crate_type = "lib"]
-#![cfg_attr(feature = "asm", feature(asm))]
 #![cfg_attr(feature = "core_intrinsics", feature(core_intrinsics))]
-#![cfg_attr(feature = "const_fn", feature(const_fn))]
 //#![warn(missing_docs)]
 
 //#![no_std]
@@ -21,12 +19,45 @@ extern crate spin;
 
 
 pub use rw_lock::*;
+pub use raw::RawBitwiseRwLock;
+pub use lock_many::*;
+pub use lock_all::*;
+pub use lock_set::*;
+pub use lock_keys::*;
+pub use read_write_lock::*;
+pub use state::LockState;
+pub use sharded::*;
+pub use seq_lock::*;
+pub use counting::*;
+pub use reentrant::*;
 pub use arch::ARCH;
 pub use helpers::random_reader_idx;
+pub use reader_idx::*;
+pub use stats::{LockStats, LockStatsSnapshot};
+pub use observer::{LockObserver, LockKind};
+#[cfg(feature = "observer")]
+pub use observer::set_global_observer;
 
+#[macro_use]
+mod macros;
 mod rw_lock;
+mod lock_many;
+mod lock_all;
+mod lock_set;
+mod lock_keys;
+mod raw;
+mod read_write_lock;
+mod state;
+mod sharded;
+mod seq_lock;
+mod counting;
+mod reentrant;
 mod util;
 mod helpers;
+mod debug;
+mod stats;
+mod observer;
+mod reader_idx;
 mod arch;
 
 //mod tests;
@@ -0,0 +1,267 @@
+use debug::*;
+use lock_many::{Backoff, LockManyError};
+use observer::LockKind;
+use rw_lock::{RwLock, ReadLockGuard, WriteLockGuard};
+use arch::ARCH;
+
+/// Out of reach of other crates: `release` and `obtained` would let safe code unlock a
+/// lock held by someone else, or make a guard without locking
+mod sealed
+{
+    /// A lock together with the mode to obtain it in, one member of a `lock_all` tuple
+    ///
+    /// # Description
+    ///
+    /// `try_obtain` makes a single attempt, and `release` undoes a successful one before any
+    /// guard exists. The reporting methods feed the stats and observers of the lock, if any.
+    pub trait LockMember
+    {
+        /// Address identifying the lock, and whether it is obtained in write mode
+        fn target(&self) -> (usize, bool);
+        
+        /// Called once before the first attempt
+        fn start(&self, reader_idx: usize);
+        
+        fn try_obtain(&self, reader_idx: usize) -> bool;
+        
+        fn release(&self, reader_idx: usize);
+        
+        /// The attempt `retry` failed because of this lock
+        fn report_retry(&self, retry: usize);
+        
+        /// The lock is held, retries are reported by `report_retry` of the lock that failed
+        fn report_acquired(&self, reader_idx: usize);
+    }
+    
+    /// A `LockMember` that turns into a guard once every member of the tuple is held
+    pub trait Obtain<'a>: LockMember
+    {
+        type Guard;
+        
+        fn obtained(self, reader_idx: usize) -> Self::Guard;
+    }
+}
+
+use self::sealed::{LockMember, Obtain};
+
+/// One member of a `lock_all` tuple, made by `read_req` or `write_req`
+///
+/// # Description
+///
+/// Sealed: only the requests of this crate implement it, and its methods can not be called
+/// from outside. Obtaining a request on its own does not lock anything:
+///
+/// ```compile_fail
+/// use spin_bitwise::*;
+///
+/// let lock = RwLock::new(0);
+/// let first = write_req(&lock).obtained(0);
+/// let second = write_req(&lock).obtained(0);
+/// ```
+pub trait LockRequest<'a>: Obtain<'a> {}
+
+impl<'a, R: Obtain<'a>> LockRequest<'a> for R {}
+
+/// Obtain `lock` in read mode as part of `lock_all`
+pub struct ReadRequest<'a, T: ? Sized + 'a>
+{
+    lock: &'a RwLock<T>,
+}
+
+/// Obtain `lock` in write mode as part of `lock_all`
+pub struct WriteRequest<'a, T: ? Sized + 'a>
+{
+    lock: &'a RwLock<T>,
+}
+
+/// Asks `lock_all` for `lock` in read mode
+pub fn read_req<'a, T: ? Sized>(lock: &'a RwLock<T>) -> ReadRequest<'a, T> {
+    ReadRequest { lock }
+}
+
+/// Asks `lock_all` for `lock` in write mode
+pub fn write_req<'a, T: ? Sized>(lock: &'a RwLock<T>) -> WriteRequest<'a, T> {
+    WriteRequest { lock }
+}
+
+impl<'a, T: ? Sized> LockMember for ReadRequest<'a, T>
+{
+    fn target(&self) -> (usize, bool) {
+        (self.lock.addr(), false)
+    }
+    
+    fn start(&self, reader_idx: usize) {
+        debug_check(&self.lock.raw.lock, Held::Read(reader_idx));
+        self.lock.observer.acquire_start(self.lock.addr(), LockKind::Read, reader_idx);
+    }
+    
+    fn try_obtain(&self, reader_idx: usize) -> bool {
+        !self.lock.raw.reserved_by_other(0) && self.lock.raw.try_lock_shared(reader_idx)
+    }
+    
+    fn release(&self, reader_idx: usize) {
+        unsafe { self.lock.raw.unlock_shared(reader_idx); }
+    }
+    
+    fn report_retry(&self, retry: usize) {
+        self.lock.report_lock_many_retry(retry);
+    }
+    
+    fn report_acquired(&self, reader_idx: usize) {
+        self.lock.report_acquired(LockKind::Read, reader_idx, 0);
+    }
+}
+
+impl<'a, T: ? Sized> Obtain<'a> for ReadRequest<'a, T>
+{
+    type Guard = ReadLockGuard<'a, T>;
+    
+    fn obtained(self, reader_idx: usize) -> ReadLockGuard<'a, T> {
+        self.lock.obtained_read(reader_idx)
+    }
+}
+
+impl<'a, T: ? Sized> LockMember for WriteRequest<'a, T>
+{
+    fn target(&self) -> (usize, bool) {
+        (self.lock.addr(), true)
+    }
+    
+    fn start(&self, _reader_idx: usize) {
+        debug_check(&self.lock.raw.lock, Held::Write);
+        self.lock.observer.acquire_start(self.lock.addr(), LockKind::Write, ARCH.reader_cnt);
+    }
+    
+    fn try_obtain(&self, _reader_idx: usize) -> bool {
+        !self.lock.raw.reserved_by_other(0) && self.lock.raw.try_lock_exclusive()
+    }
+    
+    fn release(&self, _reader_idx: usize) {
+        unsafe { self.lock.raw.unlock_exclusive(); }
+    }
+    
+    fn report_retry(&self, retry: usize) {
+        self.lock.report_lock_many_retry(retry);
+    }
+    
+    fn report_acquired(&self, _reader_idx: usize) {
+        self.lock.report_acquired(LockKind::Write, ARCH.reader_cnt, 0);
+    }
+}
+
+impl<'a, T: ? Sized> Obtain<'a> for WriteRequest<'a, T>
+{
+    type Guard = WriteLockGuard<'a, T>;
+    
+    fn obtained(self, _reader_idx: usize) -> WriteLockGuard<'a, T> {
+        self.lock.obtained_write(ARCH.reader_cnt)
+    }
+}
+
+/// A tuple of `LockRequest`s, see `lock_all`
+pub trait LockAll<'a>
+{
+    type Guards;
+    
+    fn lock_all(self, reader_idx: usize) -> Result<Self::Guards, LockManyError>;
+}
+
+/// Locks every member of the tuple `locks` at once, the way `RwLock::lock_many` does
+///
+/// # Description
+///
+/// The locks may guard data of different types. The guards come back as a tuple in the
+/// same order:
+///
+/// ```ignore
+/// let (account, mut ledger) = lock_all(reader_idx, (read_req(&account), write_req(&ledger)))?;
+/// ```
+///
+/// # Errors
+///
+/// The same checks as `RwLock::lock_many`, positions are indices into the tuple. Locks
+/// reserved by a `lock_many` call are backed off from, `lock_all` does not reserve any itself.
+pub fn lock_all<'a, L: LockAll<'a>>(reader_idx: usize, locks: L) -> Result<L::Guards, LockManyError> {
+    locks.lock_all(reader_idx)
+}
+
+fn check_members(members: &[&dyn LockMember]) -> Result<(), LockManyError> {
+    for (first, member) in members.iter().enumerate() {
+        for (second, other) in members.iter().enumerate().skip(first + 1) {
+            let ((addr, is_write), (other_addr, other_is_write)) = (member.target(), other.target());
+            
+            if addr != other_addr {
+                continue;
+            }
+            
+            return Err(match (is_write, other_is_write) {
+                (false, false) => LockManyError::Duplicate { kind: LockKind::Read, first, second },
+                (true, true) => LockManyError::Duplicate { kind: LockKind::Write, first, second },
+                (false, true) => LockManyError::Overlap { read: first, write: second },
+                (true, false) => LockManyError::Overlap { read: second, write: first },
+            });
+        }
+    }
+    
+    Ok(())
+}
+
+/// The retry loop of `lock_all`, see `RwLock::lock_many`
+fn lock_members(reader_idx: usize, members: &[&dyn LockMember]) -> Result<(), LockManyError> {
+    check_members(members)?;
+    
+    for member in members {
+        member.start(reader_idx);
+    }
+    
+    'root: for i in 0.. {
+        if i > 0 {
+            Backoff::Staggered.wait(i, members.len(), reader_idx);
+        }
+        
+        for (k, member) in members.iter().enumerate() {
+            if !member.try_obtain(reader_idx) {
+                for taken in members[..k].iter() {
+                    taken.release(reader_idx);
+                }
+                
+                member.report_retry(i);
+                continue 'root;
+            }
+        }
+        
+        for member in members {
+            member.report_acquired(reader_idx);
+        }
+        
+        break;
+    }
+    
+    Ok(())
+}
+
+macro_rules! impl_lock_all {
+    ($($name:ident . $idx:tt),+) => {
+        impl<'a, $($name: LockRequest<'a>),+> LockAll<'a> for ($($name,)+)
+        {
+            type Guards = ($(<$name as Obtain<'a>>::Guard,)+);
+            
+            fn lock_all(self, reader_idx: usize) -> Result<Self::Guards, LockManyError> {
+                let reader_idx = reader_idx % ARCH.reader_cnt;
+                
+                lock_members(reader_idx, &[$(&self.$idx as &dyn LockMember),+])?;
+                
+                Ok(($(self.$idx.obtained(reader_idx),)+))
+            }
+        }
+    }
+}
+
+impl_lock_all!(A.0);
+impl_lock_all!(A.0, B.1);
+impl_lock_all!(A.0, B.1, C.2);
+impl_lock_all!(A.0, B.1, C.2, D.3);
+impl_lock_all!(A.0, B.1, C.2, D.3, E.4);
+impl_lock_all!(A.0, B.1, C.2, D.3, E.4, F.5);
+impl_lock_all!(A.0, B.1, C.2, D.3, E.4, F.5, G.6);
+impl_lock_all!(A.0, B.1, C.2, D.3, E.4, F.5, G.6, H.7);
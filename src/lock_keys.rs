@@ -0,0 +1,91 @@
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use lock_many::LockManyError;
+use observer::LockKind;
+use rw_lock::{RwLock, ReadLockGuard, WriteLockGuard};
+
+/// The guards of `lock_keys`, by key
+pub struct LockKeys<'a, K, V: 'a>
+{
+    pub read: HashMap<K, ReadLockGuard<'a, V>>,
+    pub write: HashMap<K, WriteLockGuard<'a, V>>,
+}
+
+/// Why `lock_keys` failed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockKeysError<K>
+{
+    /// `key`, at `index` of the `kind` set (`Read` or `Write`), is not in the map
+    Missing { key: K, kind: LockKind, index: usize },
+    /// The locks were found, but the sets were refused, see `RwLock::lock_many`
+    Lock(LockManyError),
+}
+
+impl<'a, K: Hash + Eq, V> LockKeys<'a, K, V>
+{
+    /// Data behind `key`, whether it is read or written
+    pub fn get(&self, key: &K) -> Option<&V>
+    {
+        self.read.get(key).map(|guard| &**guard)
+            .or_else(|| self.write.get(key).map(|guard| &**guard))
+    }
+    
+    /// Data behind `key`, if it is written
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V>
+    {
+        self.write.get_mut(key).map(|guard| &mut **guard)
+    }
+}
+
+impl<K> From<LockManyError> for LockKeysError<K>
+{
+    fn from(err: LockManyError) -> LockKeysError<K> {
+        LockKeysError::Lock(err)
+    }
+}
+
+impl<K: fmt::Debug> fmt::Display for LockKeysError<K>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LockKeysError::Missing { ref key, kind, index } =>
+                write!(f, "key {:?} (at {}, {:?} mode) is not in the map", key, index, kind),
+            LockKeysError::Lock(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl<K: fmt::Debug> Error for LockKeysError<K> {}
+
+fn find_locks<'a, K, V, S>(map: &'a HashMap<K, RwLock<V>, S>, keys: &[K], kind: LockKind) -> Result<Vec<&'a RwLock<V>>, LockKeysError<K>>
+    where K: Hash + Eq + Clone, S: BuildHasher
+{
+    keys.iter().enumerate().map(|(index, key)| {
+        map.get(key).ok_or_else(|| LockKeysError::Missing { key: key.clone(), kind, index })
+    }).collect()
+}
+
+/// `RwLock::lock_many` over the locks stored in `map` under `reads` and `writes`
+///
+/// # Errors
+///
+/// `LockKeysError::Missing` for the first key that is not in the map, before anything is
+/// locked; otherwise the errors of `lock_many`, positions being indices into `reads` and
+/// `writes`.
+pub fn lock_keys<'a, K, V, S>(reader_idx: usize, map: &'a HashMap<K, RwLock<V>, S>, reads: &[K], writes: &[K]) -> Result<LockKeys<'a, K, V>, LockKeysError<K>>
+    where K: Hash + Eq + Clone, S: BuildHasher
+{
+    let read = find_locks(map, reads, LockKind::Read)?;
+    let write = find_locks(map, writes, LockKind::Write)?;
+    
+    let locked = RwLock::lock_many(reader_idx, &read, &write)?;
+    
+    Ok(LockKeys {
+        read: reads.iter().cloned().zip(locked.read).collect(),
+        write: writes.iter().cloned().zip(locked.write).collect(),
+    })
+}
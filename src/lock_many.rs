@@ -0,0 +1,770 @@
+use core::borrow::Borrow;
+use core::convert::TryFrom;
+use core::fmt;
+
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use util::cpu_relax;
+use helpers::*;
+use debug::*;
+use observer::LockKind;
+use rw_lock::{RwLock, ReadLockGuard, WriteLockGuard};
+use arch::ARCH;
+
+pub struct LockMany<'a, T: ? Sized + 'a> {
+    pub read: Vec<ReadLockGuard<'a, T>>,
+    pub write: Vec<WriteLockGuard<'a, T>>,
+    /// The reader index the read locks were obtained with
+    reader_idx: usize,
+}
+
+impl<'a, T: ? Sized> LockMany<'a, T>
+{
+    /// Releases `read[i]` early. Like `Vec::remove`, the guards behind it move down by one, so
+    /// `read[i + 1]` becomes `read[i]`.
+    pub fn release_read(&mut self, i: usize) {
+        drop(self.read.remove(i));
+    }
+    
+    /// Releases `write[i]` early. Like `Vec::remove`, the guards behind it move down by one, so
+    /// `write[i + 1]` becomes `write[i]`.
+    pub fn release_write(&mut self, i: usize) {
+        drop(self.write.remove(i));
+    }
+    
+    /// Turns `write[i]` into a read lock, see `WriteLockGuard::downgrade`. It moves to the end
+    /// of `read`, its index there is returned, and the guards behind it in `write` move down
+    /// by one.
+    pub fn downgrade_write(&mut self, i: usize) -> usize {
+        let guard = WriteLockGuard::downgrade(self.write.remove(i), self.reader_idx);
+        self.read.push(guard);
+        
+        self.read.len() - 1
+    }
+    
+    /// Turns `read[i]` into a write lock if no one else reads it, see
+    /// `ReadLockGuard::try_upgrade`. It moves to the end of `write`, its index there is
+    /// returned, and the guards behind it in `read` move down by one. Otherwise `read[i]`
+    /// stays where it was, as do all the other locks.
+    pub fn try_upgrade_read(&mut self, i: usize) -> Option<usize> {
+        match ReadLockGuard::try_upgrade(self.read.remove(i)) {
+            Ok(guard) => {
+                self.write.push(guard);
+                Some(self.write.len() - 1)
+            }
+            Err(guard) => {
+                self.read.insert(i, guard);
+                None
+            }
+        }
+    }
+}
+
+/// The guards of `RwLock::lock_many_array`, in the order of its `read` and `write` arrays
+pub type LockManyArray<'a, T, const R: usize, const W: usize> = ([ReadLockGuard<'a, T>; R], [WriteLockGuard<'a, T>; W]);
+
+/// Why `RwLock::lock_many` refused the sets it was given. Positions are indices into the
+/// `read` and `write` arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockManyError {
+    /// `read[read]` and `write[write]` are the same lock
+    Overlap { read: usize, write: usize },
+    /// The same lock is at both `first` and `second` of the set `kind` (`Read` or `Write`)
+    Duplicate { kind: LockKind, first: usize, second: usize },
+    /// `lock_many_with` ran out of retries or past its deadline, after `retries` retries.
+    /// `most_contended` is the lock that made the most attempts fail.
+    GaveUp { retries: usize, most_contended: Contended },
+}
+
+impl fmt::Display for LockManyError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LockManyError::Overlap { read, write } =>
+                write!(f, "lock is both read (at {}) and written (at {})", read, write),
+            LockManyError::Duplicate { kind, first, second } =>
+                write!(f, "lock is requested twice in {:?} mode (at {} and {})", kind, first, second),
+            LockManyError::GaveUp { retries, most_contended } =>
+                write!(f, "gave up after {} retries, mostly on the lock at {} ({:?} mode)", retries, most_contended.index, most_contended.kind),
+        }
+    }
+}
+
+impl Error for LockManyError {}
+
+/// The lock `try_lock_many` could not obtain: `read[index]` or `write[index]`, depending on
+/// `kind` (`Read` or `Write`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Contended
+{
+    pub index: usize,
+    pub kind: LockKind,
+}
+
+impl Contended
+{
+    fn lock<'a, T>(&self, read: &[&'a RwLock<T>], write: &[&'a RwLock<T>]) -> &'a RwLock<T> {
+        match self.kind {
+            LockKind::Write => write[self.index],
+            _ => read[self.index],
+        }
+    }
+}
+
+impl fmt::Display for Contended
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "lock at {} is held by someone else ({:?} mode requested)", self.index, self.kind)
+    }
+}
+
+impl Error for Contended {}
+
+/// How long `lock_many` waits between two attempts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backoff {
+    /// `(read.len() + write.len()) * 10` spins times a step of `0` to `3`, what `lock_many`
+    /// does. The step is `(reader_idx + retry) % 4`: it moves on with every retry, and callers
+    /// with different reader indices are on different steps.
+    Staggered,
+    /// Retry right away
+    None,
+    /// The same amount of spins every time
+    Fixed(usize),
+    /// `initial` spins, doubled on every retry up to `max`
+    Exponential { initial: usize, max: usize },
+    /// Give up the time slice of the thread
+    Yield,
+}
+
+impl Backoff
+{
+    /// Spins to wait before the attempt `retry` (starting at 1), `Yield` does not spin
+    pub fn spins(&self, retry: usize, lock_cnt: usize, reader_idx: usize) -> usize {
+        match *self {
+            Backoff::Staggered => lock_cnt * 10 * ((reader_idx + retry) % 4),
+            Backoff::None | Backoff::Yield => 0,
+            Backoff::Fixed(spins) => spins,
+            // Saturates at `max`, a shift alone would drop the bits pushed out on the left
+            Backoff::Exponential { initial, max } => u32::try_from(retry - 1).ok()
+                .and_then(|doublings| 1usize.checked_shl(doublings))
+                .and_then(|factor| initial.checked_mul(factor))
+                .map_or(max, |spins| spins.min(max)),
+        }
+    }
+    
+    /// Waits before the attempt `retry` (starting at 1)
+    pub(crate) fn wait(&self, retry: usize, lock_cnt: usize, reader_idx: usize) {
+        if *self == Backoff::Yield {
+            thread::yield_now();
+        }
+        
+        for _ in 0..self.spins(retry, lock_cnt, reader_idx) {
+            cpu_relax();
+        }
+    }
+}
+
+/// A threshold for `LockManyOptions::reserve_after` that leaves plain contention alone
+pub const RESERVE_AFTER: usize = 64;
+
+/// Limits and backoff of `RwLock::lock_many_with`. `LockManyOptions::new()` retries forever,
+/// just like `lock_many`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockManyOptions
+{
+    pub max_retries: Option<usize>,
+    pub deadline: Option<Instant>,
+    pub timeout: Option<Duration>,
+    pub backoff: Backoff,
+    pub reserve_after: Option<usize>,
+}
+
+impl LockManyOptions
+{
+    pub fn new() -> LockManyOptions {
+        LockManyOptions {
+            max_retries: None,
+            deadline: None,
+            timeout: None,
+            backoff: Backoff::Staggered,
+            reserve_after: None,
+        }
+    }
+    
+    /// Give up after `max_retries` retries, i.e. after `max_retries + 1` failed attempts. `0`
+    /// makes a single attempt.
+    pub fn max_retries(mut self, max_retries: usize) -> LockManyOptions {
+        self.max_retries = Some(max_retries);
+        self
+    }
+    
+    /// Give up once `deadline` has passed
+    pub fn deadline(mut self, deadline: Instant) -> LockManyOptions {
+        self.deadline = Some(deadline);
+        self
+    }
+    
+    /// Give up once `timeout` has passed since the call started. Unlike `deadline`, the same
+    /// options may be used for call after call.
+    pub fn timeout(mut self, timeout: Duration) -> LockManyOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+    
+    pub fn backoff(mut self, backoff: Backoff) -> LockManyOptions {
+        self.backoff = backoff;
+        self
+    }
+    
+    /// Start reserving the locks after `retries` retries, `None` (the default) never reserves
+    ///
+    /// # Description
+    ///
+    /// A reserved lock turns away every other `lock_many` call (and `lock_all`,
+    /// `try_lock_many`) until the reserving call has obtained its locks or given up, so a call
+    /// that keeps losing to smaller sets eventually gets its turn. Reservations are taken in
+    /// the order of the lock addresses, one call waiting on another's reservation never
+    /// closes a cycle. Single lock calls such as `read` and `write` ignore reservations: their
+    /// callers may already hold other locks the reserving call is waiting for.
+    ///
+    /// Only reserve if no multi-lock call on these locks is made while holding another lock.
+    /// A thread holding `b` whose `lock_many` needs `a` waits forever on a call that has
+    /// reserved `a` and waits for `b`.
+    pub fn reserve_after(mut self, retries: Option<usize>) -> LockManyOptions {
+        self.reserve_after = retries;
+        self
+    }
+    
+    /// Whether to reserve the locks before the attempt following `retries` retries
+    fn reserves(&self, retries: usize) -> bool {
+        self.reserve_after.map_or(false, |reserve_after| retries >= reserve_after)
+    }
+    
+    /// Turns the timeout into a deadline measured from now, keeping the earlier one if both are set
+    fn started(mut self) -> LockManyOptions {
+        if let Some(timeout) = self.timeout.take() {
+            let deadline = Instant::now() + timeout;
+            self.deadline = Some(self.deadline.map_or(deadline, |other| other.min(deadline)));
+        }
+        
+        self
+    }
+    
+    /// Whether any limit is set at all
+    fn is_bounded(&self) -> bool {
+        self.max_retries.is_some() || self.deadline.is_some() || self.timeout.is_some()
+    }
+    
+    /// Whether to stop instead of retrying once more, `retries` retries have been made so far
+    fn gives_up(&self, retries: usize) -> bool {
+        self.max_retries.map_or(false, |max_retries| retries >= max_retries) ||
+            self.deadline.map_or(false, |deadline| Instant::now() >= deadline)
+    }
+}
+
+impl Default for LockManyOptions
+{
+    fn default() -> LockManyOptions {
+        LockManyOptions::new()
+    }
+}
+
+/// Source of the reservation tickets, `0` stands for no reservation
+static NEXT_TICKET: AtomicUsize = AtomicUsize::new(1);
+
+/// The locks reserved by one `lock_many` call, see `LockManyOptions::reserve_after`. They
+/// are released on drop, also when an observer panics.
+struct Reservation<'a, T: 'a>
+{
+    read: &'a [&'a RwLock<T>],
+    write: &'a [&'a RwLock<T>],
+    ticket: usize,
+    /// `(address, is write, position)` sorted by address, see `sorted_lock_sets`
+    order: Vec<(usize, bool, usize)>,
+    /// Amount of entries of `order` reserved so far
+    held: usize,
+}
+
+impl<'a, T> Reservation<'a, T>
+{
+    fn new(read: &'a [&'a RwLock<T>], write: &'a [&'a RwLock<T>]) -> Reservation<'a, T> {
+        Reservation {
+            read,
+            write,
+            ticket: 0,
+            order: Vec::new(),
+            held: 0,
+        }
+    }
+    
+    /// Reserves further locks in address order, stopping at the first one reserved by
+    /// another call
+    fn extend(&mut self) {
+        if self.ticket == 0 {
+            while self.ticket == 0 {
+                self.ticket = NEXT_TICKET.fetch_add(1, Ordering::Relaxed);
+            }
+            
+            fill_sorted_lock_sets(&mut self.order, self.read.iter().map(|r| r.addr()), self.write.iter().map(|w| w.addr()));
+        }
+        
+        for &(_, is_write, pos) in self.order[self.held..].iter() {
+            let lock = if is_write { self.write[pos] } else { self.read[pos] };
+            
+            if !lock.raw.try_reserve(self.ticket) {
+                break;
+            }
+            
+            self.held += 1;
+        }
+    }
+    
+    fn release(&mut self) {
+        for &(_, is_write, pos) in self.order[..self.held].iter() {
+            let lock = if is_write { self.write[pos] } else { self.read[pos] };
+            lock.raw.unreserve();
+        }
+        
+        self.held = 0;
+    }
+}
+
+impl<'a, T> Drop for Reservation<'a, T>
+{
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Counts how often each lock of a `lock_many` call made an attempt fail
+struct Failures
+{
+    read_cnt: usize,
+    counts: Vec<usize>,
+}
+
+impl Failures
+{
+    fn new(read_cnt: usize) -> Failures {
+        Failures {
+            read_cnt,
+            counts: Vec::new(),
+        }
+    }
+    
+    fn slot(&self, contended: Contended) -> usize {
+        match contended.kind {
+            LockKind::Write => self.read_cnt + contended.index,
+            _ => contended.index,
+        }
+    }
+    
+    fn record(&mut self, contended: Contended, lock_cnt: usize) {
+        if self.counts.is_empty() {
+            self.counts.resize(lock_cnt, 0);
+        }
+        
+        let slot = self.slot(contended);
+        self.counts[slot] += 1;
+    }
+    
+    fn most_contended(&self) -> Contended {
+        let (slot, _) = self.counts.iter().enumerate().fold((0, 0), |most, (slot, &count)| {
+            if count > most.1 { (slot, count) } else { most }
+        });
+        
+        if slot < self.read_cnt {
+            Contended { index: slot, kind: LockKind::Read }
+        } else {
+            Contended { index: slot - self.read_cnt, kind: LockKind::Write }
+        }
+    }
+}
+
+/// `(address, is write, position)` of every lock in `read` and `write`, sorted by address
+pub(crate) fn sorted_lock_sets<R, W>(read: R, write: W) -> Vec<(usize, bool, usize)>
+    where R: Iterator<Item = usize>, W: Iterator<Item = usize>
+{
+    let mut entries = Vec::new();
+    
+    fill_sorted_lock_sets(&mut entries, read, write);
+    
+    entries
+}
+
+/// `sorted_lock_sets` into an existing buffer
+pub(crate) fn fill_sorted_lock_sets<R, W>(entries: &mut Vec<(usize, bool, usize)>, read: R, write: W)
+    where R: Iterator<Item = usize>, W: Iterator<Item = usize>
+{
+    entries.clear();
+    entries.extend(read.enumerate().map(|(pos, addr)| (addr, false, pos)));
+    entries.extend(write.enumerate().map(|(pos, addr)| (addr, true, pos)));
+    entries.sort();
+}
+
+/// Looks for the same lock address twice among `read` and `write`
+///
+/// # Description
+///
+/// Entries are sorted by address, so a repeated lock ends up next to itself: `O(n log n)`
+/// instead of comparing every pair.
+pub(crate) fn check_lock_sets<R, W>(read: R, write: W) -> Result<(), LockManyError>
+    where R: Iterator<Item = usize>, W: Iterator<Item = usize>
+{
+    check_sorted_lock_sets(&sorted_lock_sets(read, write))
+}
+
+/// `check_lock_sets` without the allocation, comparing every pair instead
+pub(crate) fn check_lock_pairs<T>(read: &[&RwLock<T>], write: &[&RwLock<T>]) -> Result<(), LockManyError> {
+    for (first, r) in read.iter().enumerate() {
+        for (second, other) in read.iter().enumerate().skip(first + 1) {
+            if r.addr() == other.addr() {
+                return Err(LockManyError::Duplicate { kind: LockKind::Read, first, second });
+            }
+        }
+        
+        for (pos, w) in write.iter().enumerate() {
+            if r.addr() == w.addr() {
+                return Err(LockManyError::Overlap { read: first, write: pos });
+            }
+        }
+    }
+    
+    for (first, w) in write.iter().enumerate() {
+        for (second, other) in write.iter().enumerate().skip(first + 1) {
+            if w.addr() == other.addr() {
+                return Err(LockManyError::Duplicate { kind: LockKind::Write, first, second });
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Turns the `read` / `write` arguments of the `lock_many` family into a `Vec` of locks
+fn collect_locks<'a, T, I>(locks: I) -> Vec<&'a RwLock<T>>
+    where I: IntoIterator, I::Item: Borrow<&'a RwLock<T>>
+{
+    locks.into_iter().map(|lock| *lock.borrow()).collect()
+}
+
+pub(crate) fn check_sorted_lock_sets(entries: &[(usize, bool, usize)]) -> Result<(), LockManyError> {
+    for pair in entries.windows(2) {
+        let ((addr, is_write, pos), (other_addr, other_is_write, other_pos)) = (pair[0], pair[1]);
+        
+        if addr != other_addr {
+            continue;
+        }
+        
+        return Err(match (is_write, other_is_write) {
+            (false, false) => LockManyError::Duplicate { kind: LockKind::Read, first: pos, second: other_pos },
+            (true, true) => LockManyError::Duplicate { kind: LockKind::Write, first: pos, second: other_pos },
+            _ => LockManyError::Overlap { read: pos, write: other_pos },
+        });
+    }
+    
+    Ok(())
+}
+
+impl<T> RwLock<T>
+{
+    /// Locks all readers and writers at once
+    ///
+    /// # Arguments
+    ///
+    /// * `reader_idx` - an id for the readers (see examples)
+    /// * `read` - a set of locks to be locked in reading mode
+    /// * `write` - a set of locks to be lock in writing mode
+    ///
+    /// The sets may be anything that iterates over `&RwLock` or `&&RwLock`: `&Vec<&RwLock>`,
+    /// slices, arrays or iterators. The guards come out in the same order. An empty set needs
+    /// its type spelled out, e.g. `&[] as &[&RwLock<T>]`.
+    ///
+    /// A call with a large set may keep losing to calls with smaller ones, see
+    /// `LockManyOptions::reserve_after` for a way out.
+    ///
+    /// # Errors
+    ///
+    /// A lock that is in both sets, or twice in the same set, could never be obtained. Such
+    /// sets are refused with a `LockManyError` before anything is locked.
+    pub fn lock_many<'a, R, W>(reader_idx: usize, read: R, write: W) -> Result<LockMany<'a, T>, LockManyError>
+        where R: IntoIterator, R::Item: Borrow<&'a Self>, W: IntoIterator, W::Item: Borrow<&'a Self>
+    {
+        Self::lock_many_with(reader_idx, read, write, LockManyOptions::new())
+    }
+    
+    /// `lock_many` with a limit on the retries or on the time spent, and a choice of backoff
+    ///
+    /// # Errors
+    ///
+    /// Besides the checks of `lock_many`, returns `LockManyError::GaveUp` once the limits of
+    /// `options` are reached. Nothing is held at that point.
+    pub fn lock_many_with<'a, R, W>(reader_idx: usize, read: R, write: W, options: LockManyOptions) -> Result<LockMany<'a, T>, LockManyError>
+        where R: IntoIterator, R::Item: Borrow<&'a Self>, W: IntoIterator, W::Item: Borrow<&'a Self>
+    {
+        let (read, write) = (collect_locks(read), collect_locks(write));
+        
+        check_lock_sets(read.iter().map(|r| r.addr()), write.iter().map(|w| w.addr()))?;
+        
+        let reader_idx = reader_idx % ARCH.reader_cnt;
+        
+        Self::lock_loop(reader_idx, &read, &write, options)?;
+        
+        Ok(Self::obtained_many(reader_idx, &read, &write))
+    }
+    
+    /// `lock_many` for sets of a fixed size, without any heap allocation
+    ///
+    /// # Errors
+    ///
+    /// The same checks as `lock_many`.
+    pub fn lock_many_array<'a, const R: usize, const W: usize>(reader_idx: usize, read: [&'a Self; R], write: [&'a Self; W])
+        -> Result<LockManyArray<'a, T, R, W>, LockManyError> {
+        check_lock_pairs(&read, &write)?;
+        
+        let reader_idx = reader_idx % ARCH.reader_cnt;
+        
+        Self::lock_loop(reader_idx, &read, &write, LockManyOptions::new())?;
+        
+        Ok((
+            read.map(|r| r.obtained_read(reader_idx)),
+            write.map(|w| w.obtained_write(ARCH.reader_cnt)),
+        ))
+    }
+    
+    /// Locks all readers and writers one after another, in the order of their addresses
+    ///
+    /// # Description
+    ///
+    /// Instead of releasing everything on a conflict like `lock_many`, waits for the contended
+    /// lock while keeping the ones it already holds. Every caller takes the locks in the same
+    /// global order, so no two of them can wait on each other. Mixing this with `lock_many` is
+    /// fine too, as `lock_many` releases what it holds before waiting again. Only a `lock_many`
+    /// call that reserves its locks keeps them reserved while waiting, see
+    /// `LockManyOptions::reserve_after`.
+    ///
+    /// # Errors
+    ///
+    /// The same checks as `lock_many`.
+    pub fn lock_many_ordered<'a, R, W>(reader_idx: usize, read: R, write: W) -> Result<LockMany<'a, T>, LockManyError>
+        where R: IntoIterator, R::Item: Borrow<&'a Self>, W: IntoIterator, W::Item: Borrow<&'a Self>
+    {
+        let (read, write) = (collect_locks(read), collect_locks(write));
+        
+        let entries = sorted_lock_sets(read.iter().map(|r| r.addr()), write.iter().map(|w| w.addr()));
+        check_sorted_lock_sets(&entries)?;
+        
+        let reader_idx = reader_idx % ARCH.reader_cnt;
+        
+        Self::lock_ordered(reader_idx, &read, &write, &entries);
+        
+        Ok(Self::obtained_many(reader_idx, &read, &write))
+    }
+    
+    /// Takes the locks in the order of `entries`, see `sorted_lock_sets`
+    pub(crate) fn lock_ordered(reader_idx: usize, read: &[&Self], write: &[&Self], entries: &[(usize, bool, usize)]) {
+        for &(_, is_write, pos) in entries.iter() {
+            if is_write {
+                let w = write[pos];
+                
+                debug_check(&w.raw.lock, Held::Write);
+                w.observer.acquire_start(w.addr(), LockKind::Write, ARCH.reader_cnt);
+                
+                let spins = w.raw.obtain_exclusive();
+                w.report_acquired(LockKind::Write, ARCH.reader_cnt, spins);
+            } else {
+                let r = read[pos];
+                
+                debug_check(&r.raw.lock, Held::Read(reader_idx));
+                r.observer.acquire_start(r.addr(), LockKind::Read, reader_idx);
+                
+                let spins = r.raw.obtain_shared(reader_idx);
+                r.report_acquired(LockKind::Read, reader_idx, spins);
+            }
+        }
+    }
+    
+    /// Makes a single attempt at `lock_many`
+    ///
+    /// # Description
+    ///
+    /// Everything obtained so far is released again as soon as one lock is held by someone
+    /// else, so the caller may move on to other work instead of waiting. The sets are not
+    /// checked for overlaps, a lock that is requested twice is reported as contended, and so
+    /// is a lock reserved by a `lock_many` call.
+    ///
+    /// # Errors
+    ///
+    /// `Contended` names the first lock that could not be obtained.
+    pub fn try_lock_many<'a, R, W>(reader_idx: usize, read: R, write: W) -> Result<LockMany<'a, T>, Contended>
+        where R: IntoIterator, R::Item: Borrow<&'a Self>, W: IntoIterator, W::Item: Borrow<&'a Self>
+    {
+        let (read, write) = (collect_locks(read), collect_locks(write));
+        let reader_idx = reader_idx % ARCH.reader_cnt;
+        
+        for r in read.iter() {
+            r.observer.acquire_start(r.addr(), LockKind::Read, reader_idx);
+        }
+        
+        for w in write.iter() {
+            w.observer.acquire_start(w.addr(), LockKind::Write, ARCH.reader_cnt);
+        }
+        
+        Self::lock_pass(reader_idx, &read, &write, 0)?;
+        Self::report_lock_pass(reader_idx, &read, &write);
+        
+        Ok(Self::obtained_many(reader_idx, &read, &write))
+    }
+    
+    /// The retry loop of `lock_many`. Only allocates to count the failures, and only if
+    /// `options` has a limit, and to sort the locks once it starts reserving them.
+    pub(crate) fn lock_loop(reader_idx: usize, read: &[&Self], write: &[&Self], options: LockManyOptions) -> Result<(), LockManyError> {
+        // TODO: check if idx is < ARCH.reader_cnt
+        
+        let options = options.started();
+        let lock_cnt = read.len() + write.len();
+        
+        for r in read {
+            debug_check(&r.raw.lock, Held::Read(reader_idx));
+            r.observer.acquire_start(r.addr(), LockKind::Read, reader_idx);
+        }
+        
+        for w in write {
+            debug_check(&w.raw.lock, Held::Write);
+            w.observer.acquire_start(w.addr(), LockKind::Write, ARCH.reader_cnt);
+        }
+        
+        let mut failures = Failures::new(read.len());
+        let mut reservation = Reservation::new(read, write);
+        
+        for i in 0.. {
+            if i > 0 {
+                // TODO: We may want to increase the wait time here depending on the location.
+                
+                // TODO: this is a very picky one. 2-thread programs may sync quite well
+                // TODO: and reduce the performance by 10-x.
+                options.backoff.wait(i, lock_cnt, reader_idx);
+            }
+            
+            if options.reserves(i) {
+                reservation.extend();
+            }
+            
+            match Self::lock_pass(reader_idx, read, write, reservation.ticket) {
+                Ok(()) => {
+                    Self::report_lock_pass(reader_idx, read, write);
+                    break;
+                }
+                Err(contended) => {
+                    contended.lock(read, write).report_lock_many_retry(i);
+                    
+                    if !options.is_bounded() {
+                        continue;
+                    }
+                    
+                    failures.record(contended, lock_cnt);
+                    
+                    if options.gives_up(i) {
+                        return Err(LockManyError::GaveUp { retries: i, most_contended: failures.most_contended() });
+                    }
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Goes over the sets once. On the first conflict every lock obtained so far, i.e. those
+    /// in front of the contended one, is released again. Locks reserved by a call other than
+    /// the one holding `ticket` count as contended.
+    fn lock_pass(reader_idx: usize, read: &[&Self], write: &[&Self], ticket: usize) -> Result<(), Contended> {
+        let contended = Self::lock_pass_inner(reader_idx, read, write, ticket);
+        
+        if let Err(contended) = contended {
+            let (read_cnt, write_cnt) = match contended.kind {
+                LockKind::Write => (read.len(), contended.index),
+                _ => (contended.index, 0),
+            };
+            
+            for r in read[..read_cnt].iter() {
+                atomic_reader_unlock(&r.raw.lock, reader_idx);
+            }
+            
+            for w in write[..write_cnt].iter() {
+                atomic_writer_unlock(&w.raw.lock);
+            }
+        }
+        
+        contended
+    }
+    
+    #[inline(always)]
+    fn lock_pass_inner(reader_idx: usize, read: &[&Self], write: &[&Self], ticket: usize) -> Result<(), Contended> {
+        for (index, &r) in read.iter().enumerate() {
+            if r.raw.reserved_by_other(ticket) {
+                return Err(Contended { index, kind: LockKind::Read });
+            }
+            
+            let (_, owned, block) = atomic_reader_lock(&r.raw.lock, reader_idx);
+            
+            if owned && block {
+                atomic_reader_unlock(&r.raw.lock, reader_idx);
+                return Err(Contended { index, kind: LockKind::Read });
+            } else if !owned {
+                return Err(Contended { index, kind: LockKind::Read });
+            }
+        }
+        
+        for (index, &w) in write.iter().enumerate() {
+            if w.raw.reserved_by_other(ticket) {
+                return Err(Contended { index, kind: LockKind::Write });
+            }
+            
+            let (_, owned, block) = atomic_writer_lock(&w.raw.lock);
+            if owned && !atomic_overflow_free(&w.raw.overflow) {
+                atomic_writer_unlock(&w.raw.lock);
+                return Err(Contended { index, kind: LockKind::Write });
+            } else if owned {
+                w.raw.bump_version();
+            }
+            
+            if block || !owned {
+                return Err(Contended { index, kind: LockKind::Write });
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Reports the locks as obtained without any failed attempt: the retries of the call are
+    /// already blamed on the locks that caused them, see `report_lock_many_retry`
+    fn report_lock_pass(reader_idx: usize, read: &[&Self], write: &[&Self]) {
+        for r in read.iter() {
+            r.report_acquired(LockKind::Read, reader_idx, 0);
+        }
+        
+        for w in write.iter() {
+            w.report_acquired(LockKind::Write, ARCH.reader_cnt, 0);
+        }
+    }
+    
+    fn obtained_many<'a>(reader_idx: usize, read: &[&'a Self], write: &[&'a Self]) -> LockMany<'a, T> {
+        LockMany::<'a, T> {
+            read: read.iter().map(
+                |args| {
+                    let x = *args;
+                    x.obtained_read(reader_idx)
+                }).collect(),
+            write: write.iter().map(
+                |args| {
+                    let x = *args;
+                    x.obtained_write(ARCH.reader_cnt)
+                }).collect(),
+            reader_idx,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use core::ops::Drop;
+
+use lock_many::*;
+use rw_lock::{RwLock, ReadLockGuard, WriteLockGuard};
+use arch::ARCH;
+
+/// A set of locks to be obtained together over and over, the reusable form of `RwLock::lock_many`
+///
+/// # Description
+///
+/// The locks, the guards and the address order live in buffers that keep their capacity
+/// across `clear()` and `acquire()`, so a loop acquiring the same kind of set does not
+/// allocate once the buffers have grown. The order and the overlap check are computed once
+/// after the set changes and reused by every following acquisition.
+pub struct LockSet<'a, T: 'a>
+{
+    read: Vec<&'a RwLock<T>>,
+    write: Vec<&'a RwLock<T>>,
+    read_guards: Vec<ReadLockGuard<'a, T>>,
+    write_guards: Vec<WriteLockGuard<'a, T>>,
+    /// `(address, is write, position)` sorted by address, see `sorted_lock_sets`
+    order: Vec<(usize, bool, usize)>,
+    /// Result of the overlap check, `None` if the set has changed since
+    checked: Option<Result<(), LockManyError>>,
+}
+
+/// The locks of a `LockSet` while they are held, released once dropped
+pub struct LockManyGuard<'s, 'a: 's, T: 'a>
+{
+    set: &'s mut LockSet<'a, T>,
+}
+
+impl<'a, T> LockSet<'a, T>
+{
+    pub fn new() -> LockSet<'a, T>
+    {
+        LockSet {
+            read: Vec::new(),
+            write: Vec::new(),
+            read_guards: Vec::new(),
+            write_guards: Vec::new(),
+            order: Vec::new(),
+            checked: None,
+        }
+    }
+    
+    pub fn add_read(&mut self, lock: &'a RwLock<T>) -> &mut LockSet<'a, T>
+    {
+        self.read.push(lock);
+        self.checked = None;
+        self
+    }
+    
+    pub fn add_write(&mut self, lock: &'a RwLock<T>) -> &mut LockSet<'a, T>
+    {
+        self.write.push(lock);
+        self.checked = None;
+        self
+    }
+    
+    /// Removes all locks, keeping the buffers
+    pub fn clear(&mut self)
+    {
+        self.read.clear();
+        self.write.clear();
+        self.checked = None;
+    }
+    
+    /// Sorts the set and checks it for overlaps, unless that has been done since the last change
+    fn prepare(&mut self) -> Result<(), LockManyError>
+    {
+        if let Some(checked) = self.checked {
+            return checked;
+        }
+        
+        fill_sorted_lock_sets(&mut self.order, self.read.iter().map(|r| r.addr()), self.write.iter().map(|w| w.addr()));
+        
+        let checked = check_sorted_lock_sets(&self.order);
+        self.checked = Some(checked);
+        
+        checked
+    }
+    
+    fn obtained<'s>(&'s mut self, reader_idx: usize) -> LockManyGuard<'s, 'a, T>
+    {
+        self.read_guards.extend(self.read.iter().map(|r| r.obtained_read(reader_idx)));
+        self.write_guards.extend(self.write.iter().map(|w| w.obtained_write(ARCH.reader_cnt)));
+        
+        LockManyGuard {
+            set: self,
+        }
+    }
+    
+    /// Locks the whole set the way `RwLock::lock_many` does
+    ///
+    /// # Errors
+    ///
+    /// The same checks as `RwLock::lock_many`.
+    pub fn acquire<'s>(&'s mut self, reader_idx: usize) -> Result<LockManyGuard<'s, 'a, T>, LockManyError>
+    {
+        self.acquire_with(reader_idx, LockManyOptions::new())
+    }
+    
+    /// Locks the whole set the way `RwLock::lock_many_with` does
+    pub fn acquire_with<'s>(&'s mut self, reader_idx: usize, options: LockManyOptions) -> Result<LockManyGuard<'s, 'a, T>, LockManyError>
+    {
+        self.prepare()?;
+        
+        let reader_idx = reader_idx % ARCH.reader_cnt;
+        
+        RwLock::lock_loop(reader_idx, &self.read, &self.write, options)?;
+        
+        Ok(self.obtained(reader_idx))
+    }
+    
+    /// Locks the whole set the way `RwLock::lock_many_ordered` does, using the stored order
+    pub fn acquire_ordered<'s>(&'s mut self, reader_idx: usize) -> Result<LockManyGuard<'s, 'a, T>, LockManyError>
+    {
+        self.prepare()?;
+        
+        let reader_idx = reader_idx % ARCH.reader_cnt;
+        
+        RwLock::lock_ordered(reader_idx, &self.read, &self.write, &self.order);
+        
+        Ok(self.obtained(reader_idx))
+    }
+}
+
+impl<'a, T> Default for LockSet<'a, T>
+{
+    fn default() -> LockSet<'a, T> {
+        LockSet::new()
+    }
+}
+
+impl<'s, 'a, T> LockManyGuard<'s, 'a, T>
+{
+    /// Guards of the read locks, in the order they were added
+    pub fn read(&self) -> &[ReadLockGuard<'a, T>]
+    {
+        &self.set.read_guards
+    }
+    
+    /// Guards of the write locks, in the order they were added
+    pub fn write(&mut self) -> &mut [WriteLockGuard<'a, T>]
+    {
+        &mut self.set.write_guards
+    }
+    
+    /// Both `read()` and `write()` at once
+    pub fn split(&mut self) -> (&[ReadLockGuard<'a, T>], &mut [WriteLockGuard<'a, T>])
+    {
+        (&self.set.read_guards, &mut self.set.write_guards)
+    }
+}
+
+impl<'s, 'a, T> Drop for LockManyGuard<'s, 'a, T>
+{
+    fn drop(&mut self)
+    {
+        self.set.read_guards.clear();
+        self.set.write_guards.clear();
+    }
+}
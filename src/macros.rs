@@ -0,0 +1,20 @@
+macro_rules! define_deref_for {
+    ($cls:path) => (
+        impl<'a, T: ? Sized> Deref for $cls
+        {
+            type Target = T;
+            fn deref(&self) -> &T { &*self.data }
+        }
+    )
+}
+
+macro_rules! define_deref_mut_for {
+    ($cls:path) => (
+        impl<'a, T: ? Sized> DerefMut for $cls
+        {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut *self.data
+            }
+        }
+    )
+}
@@ -0,0 +1,190 @@
+//! Acquire / release hooks for tracing, enabled by the `observer` feature.
+//!
+//! Without the feature `Observer` and `ReleaseHook` are empty and every method here is a no-op.
+
+use std::marker::PhantomData;
+
+#[cfg(feature = "observer")]
+use std::ptr;
+#[cfg(feature = "observer")]
+use std::sync::Arc;
+#[cfg(feature = "observer")]
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+/// The mode a lock is obtained in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockKind {
+    /// Read mode through the reader slot `idx`
+    Read,
+    /// Read mode through the overflow counter, see `RwLock::read_or_overflow`
+    Overflow,
+    /// Write mode, `idx` is `ARCH.reader_cnt`
+    Write,
+}
+
+/// Receives the events of the locks it is registered with
+///
+/// # Description
+///
+/// `lock` is the address of the lock the event is about, `idx` the reader slot. It is
+/// `ARCH.reader_cnt` for the writer, and for `read_any` as long as no slot has been found.
+/// All methods do nothing by default.
+///
+/// The callbacks are invoked from within the locking loops, so they should be cheap and must
+/// not obtain the lock they are called for.
+pub trait LockObserver
+{
+    /// Called before the first attempt at obtaining the lock, by the single attempt `try_*`
+    /// methods as well. A failed attempt is followed by neither `on_acquired` nor `on_release`.
+    fn on_acquire_start(&self, _lock: usize, _kind: LockKind, _idx: usize) {}
+    
+    /// Called once the lock is obtained after `spins` failed attempts. Multi-lock calls report
+    /// their failed attempts through `on_lock_many_retry` instead, and `0` here.
+    fn on_acquired(&self, _lock: usize, _kind: LockKind, _idx: usize, _spins: usize) {}
+    
+    /// Called right after the lock has been released
+    fn on_release(&self, _lock: usize, _kind: LockKind, _idx: usize) {}
+    
+    /// Called when `lock_many` has to start over because `lock` could not be obtained
+    fn on_lock_many_retry(&self, _lock: usize, _retry: usize) {}
+}
+
+/// The observer of every lock, null until `set_global_observer` succeeds. Once set it is
+/// never freed.
+#[cfg(feature = "observer")]
+static GLOBAL_OBSERVER: AtomicPtr<Box<dyn LockObserver + Send + Sync>> = AtomicPtr::new(ptr::null_mut());
+
+/// Registers an observer for every lock. Can only be done once, returns false if an observer
+/// has already been registered.
+#[cfg(feature = "observer")]
+pub fn set_global_observer<O: LockObserver + Send + Sync + 'static>(observer: O) -> bool {
+    let observer: Box<dyn LockObserver + Send + Sync> = Box::new(observer);
+    let observer = Box::into_raw(Box::new(observer));
+    
+    match GLOBAL_OBSERVER.compare_exchange(ptr::null_mut(), observer, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => true,
+        Err(_) => {
+            drop(unsafe { Box::from_raw(observer) });
+            false
+        }
+    }
+}
+
+#[cfg(feature = "observer")]
+#[inline(always)]
+fn global_observer() -> Option<&'static (dyn LockObserver + Send + Sync)> {
+    let observer = GLOBAL_OBSERVER.load(Ordering::Acquire);
+    
+    if observer.is_null() {
+        None
+    } else {
+        Some(unsafe { &**observer })
+    }
+}
+
+/// The observer registered with a single lock, in addition to the global one
+#[derive(Default)]
+pub struct Observer
+{
+    #[cfg(feature = "observer")]
+    observer: Option<Arc<dyn LockObserver + Send + Sync>>,
+}
+
+/// Reports the release of a guard once it is dropped, part of every guard of an observed lock
+pub struct ReleaseHook<'a>
+{
+    #[cfg(feature = "observer")]
+    observer: &'a Observer,
+    #[cfg(feature = "observer")]
+    lock: usize,
+    #[cfg(feature = "observer")]
+    kind: LockKind,
+    #[cfg(feature = "observer")]
+    idx: usize,
+    _lifetime: PhantomData<&'a Observer>,
+}
+
+#[cfg(feature = "observer")]
+impl Observer
+{
+    pub(crate) fn new() -> Observer {
+        Observer {
+            observer: None,
+        }
+    }
+    
+    pub(crate) fn with_observer(observer: Arc<dyn LockObserver + Send + Sync>) -> Observer {
+        Observer {
+            observer: Some(observer),
+        }
+    }
+    
+    #[inline(always)]
+    fn each<F: Fn(&dyn LockObserver)>(&self, f: F) {
+        if let Some(ref observer) = self.observer {
+            f(&**observer);
+        }
+        
+        if let Some(observer) = global_observer() {
+            f(observer);
+        }
+    }
+    
+    pub(crate) fn acquire_start(&self, lock: usize, kind: LockKind, idx: usize) {
+        self.each(|o| o.on_acquire_start(lock, kind, idx));
+    }
+    
+    pub(crate) fn acquired(&self, lock: usize, kind: LockKind, idx: usize, spins: usize) {
+        self.each(|o| o.on_acquired(lock, kind, idx, spins));
+    }
+    
+    pub(crate) fn lock_many_retry(&self, lock: usize, retry: usize) {
+        self.each(|o| o.on_lock_many_retry(lock, retry));
+    }
+    
+    pub(crate) fn release_hook(&self, lock: usize, kind: LockKind, idx: usize) -> ReleaseHook<'_> {
+        ReleaseHook {
+            observer: self,
+            lock,
+            kind,
+            idx,
+            _lifetime: PhantomData,
+        }
+    }
+}
+
+#[cfg(not(feature = "observer"))]
+impl Observer
+{
+    #[inline(always)]
+    pub(crate) fn new() -> Observer {
+        Observer {}
+    }
+    
+    #[inline(always)]
+    pub(crate) fn acquire_start(&self, _lock: usize, _kind: LockKind, _idx: usize) {}
+    
+    #[inline(always)]
+    pub(crate) fn acquired(&self, _lock: usize, _kind: LockKind, _idx: usize, _spins: usize) {}
+    
+    #[inline(always)]
+    pub(crate) fn lock_many_retry(&self, _lock: usize, _retry: usize) {}
+    
+    #[inline(always)]
+    pub(crate) fn release_hook(&self, _lock: usize, _kind: LockKind, _idx: usize) -> ReleaseHook<'_> {
+        ReleaseHook {
+            _lifetime: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "observer")]
+impl<'a> Drop for ReleaseHook<'a>
+{
+    fn drop(&mut self)
+    {
+        let (lock, kind, idx) = (self.lock, self.kind, self.idx);
+        
+        self.observer.each(|o| o.on_release(lock, kind, idx));
+    }
+}
@@ -0,0 +1,267 @@
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use util::cpu_relax;
+use helpers::*;
+use state::LockState;
+use arch::ARCH;
+
+/// The lock word of `RwLock` without any data attached to it
+///
+/// # Description
+///
+/// Guards external resources such as file descriptors or mapped memory, where a guard tied to
+/// a borrow of the lock does not fit. Obtaining the lock is safe, releasing it is `unsafe`:
+/// the caller has to make sure it actually holds the lock in the mode (and with the reader
+/// index) it releases.
+pub struct RawBitwiseRwLock
+{
+    pub(crate) lock: AtomicUsize,
+    /// Bumped on every writer acquisition, all bits of `lock` are already taken by the readers
+    /// and the writer.
+    pub(crate) version: AtomicUsize,
+    /// Readers that could not get their own slot, see `RwLock::read_or_overflow`
+    pub(crate) overflow: AtomicUsize,
+    /// Ticket of the `lock_many` call that has reserved the lock, `0` if none, see
+    /// `LockManyOptions::reserve_after`
+    pub(crate) reserved: AtomicUsize,
+}
+
+impl RawBitwiseRwLock
+{
+    /// A free lock, `const` so it may be kept in a `static`
+    pub const fn new() -> RawBitwiseRwLock
+    {
+        RawBitwiseRwLock {
+            lock: AtomicUsize::new(0),
+            version: AtomicUsize::new(0),
+            overflow: AtomicUsize::new(0),
+            reserved: AtomicUsize::new(0),
+        }
+    }
+    
+    /// Sets the reader bit `idx`, returns the amount of failed attempts
+    #[inline(always)]
+    pub(crate) fn obtain_shared(&self, idx: usize) -> usize {
+        // TODO: check if idx is < ARCH.reader_cnt
+        
+        let mut spins = 0;
+        
+        loop {
+            let (_, owned, block) = atomic_reader_lock(&self.lock, idx);
+            if owned && !block {
+                cpu_relax();
+                break
+            } else if owned {
+                atomic_reader_unlock(&self.lock, idx);
+                cpu_relax();
+            } else {
+                cpu_relax();
+            }
+            
+            spins += 1;
+        }
+        
+        spins
+    }
+    
+    /// Sets the writer bit and waits for the overflowing readers to leave, returns the amount
+    /// of failed attempts
+    #[inline(always)]
+    pub(crate) fn obtain_exclusive(&self) -> usize {
+        let mut spins = 0;
+        
+        loop {
+            let (_, owned, block) = atomic_writer_lock(&self.lock);
+            
+            if owned && !block {
+                while !atomic_overflow_free(&self.overflow) {
+                    spins += 1;
+                    cpu_relax();
+                }
+                
+                self.bump_version();
+                break;
+            } else {
+                spins += 1;
+                cpu_relax();
+            }
+        }
+        
+        spins
+    }
+    
+    /// Called right after the writer bit has been claimed. Any optimistic reader that has
+    /// taken a stamp before this point fails validation.
+    #[inline(always)]
+    pub(crate) fn bump_version(&self) {
+        self.version.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+    }
+    
+    /// Obtain the lock in read mode
+    ///
+    /// # Arguments
+    /// * `idx` - reader index
+    ///
+    pub fn lock_shared(&self, idx: usize)
+    {
+        self.obtain_shared(idx);
+    }
+    
+    /// Makes a single attempt at `lock_shared`, returns true if the lock has been obtained
+    pub fn try_lock_shared(&self, idx: usize) -> bool
+    {
+        let (_, owned, block) = atomic_reader_lock(&self.lock, idx);
+        
+        if owned && block {
+            atomic_reader_unlock(&self.lock, idx);
+        }
+        
+        owned && !block
+    }
+    
+    /// Release the lock obtained with `lock_shared(idx)`
+    ///
+    /// # Safety
+    ///
+    /// The reader bit `idx` must be held by the caller.
+    pub unsafe fn unlock_shared(&self, idx: usize)
+    {
+        atomic_reader_unlock(&self.lock, idx);
+    }
+    
+    /// Obtain the lock in write mode
+    pub fn lock_exclusive(&self)
+    {
+        self.obtain_exclusive();
+    }
+    
+    /// Makes a single attempt at `lock_exclusive`, returns true if the lock has been obtained
+    pub fn try_lock_exclusive(&self) -> bool
+    {
+        let (_, owned, _) = atomic_writer_lock(&self.lock);
+        
+        if owned && !atomic_overflow_free(&self.overflow) {
+            atomic_writer_unlock(&self.lock);
+            false
+        } else if owned {
+            self.bump_version();
+            true
+        } else {
+            false
+        }
+    }
+    
+    /// Release the lock obtained with `lock_exclusive()`
+    ///
+    /// # Safety
+    ///
+    /// The writer bit must be held by the caller.
+    pub unsafe fn unlock_exclusive(&self)
+    {
+        atomic_writer_unlock(&self.lock);
+    }
+    
+    /// Turns the write lock into a read lock on the reader slot `idx`, no other writer can get
+    /// in between
+    ///
+    /// # Safety
+    ///
+    /// The writer bit must be held by the caller.
+    pub unsafe fn downgrade(&self, idx: usize)
+    {
+        // Readers turned away by the writer set and clear their bit on the way out, wait for
+        // the one using `idx` to leave
+        while !atomic_reader_lock(&self.lock, idx).1 {
+            cpu_relax();
+        }
+        
+        atomic_writer_unlock(&self.lock);
+    }
+    
+    /// Turns the read lock on the reader slot `idx` into the write lock if no other reader
+    /// holds the lock. Returns false, still holding the read lock, otherwise.
+    ///
+    /// # Safety
+    ///
+    /// The reader bit `idx` must be held by the caller.
+    pub unsafe fn try_upgrade(&self, idx: usize) -> bool
+    {
+        let writer = bitmask_lock(ARCH.reader_cnt);
+        
+        // Sequentially consistent for the overflow counter, see `atomic_writer_lock`
+        if self.lock.compare_exchange(bitmask_lock(idx), writer, Ordering::SeqCst, Ordering::Relaxed).is_err() {
+            return false;
+        }
+        
+        if !atomic_overflow_free(&self.overflow) {
+            self.downgrade(idx);
+            return false;
+        }
+        
+        self.bump_version();
+        true
+    }
+    
+    /// Decodes the current state of the lock
+    pub fn state(&self) -> LockState
+    {
+        let state = atomic_load(&self.lock);
+        
+        LockState::decode(
+            state,
+            self.overflow.load(Ordering::Relaxed),
+            self.version.load(Ordering::Relaxed),
+            self.reserved.load(Ordering::Relaxed) != 0,
+        )
+    }
+    
+    /// Returns true if the lock is held in any mode
+    pub fn is_locked(&self) -> bool
+    {
+        self.state().is_locked()
+    }
+    
+    /// Returns true if the lock is held in write mode
+    pub fn is_write_locked(&self) -> bool
+    {
+        !atomic_writer_free(&self.lock)
+    }
+    
+    /// Amount of readers currently holding the lock
+    pub fn reader_count(&self) -> usize
+    {
+        self.state().reader_count()
+    }
+    
+    /// Whether a `lock_many` call other than the one holding `ticket` has reserved the lock
+    #[inline(always)]
+    pub(crate) fn reserved_by_other(&self, ticket: usize) -> bool {
+        let reserved = self.reserved.load(Ordering::Acquire);
+        reserved != 0 && reserved != ticket
+    }
+    
+    /// Reserves the lock for the `lock_many` call holding `ticket`, unless it is already reserved
+    #[inline(always)]
+    pub(crate) fn try_reserve(&self, ticket: usize) -> bool {
+        self.reserved.compare_exchange(0, ticket, Ordering::AcqRel, Ordering::Relaxed).is_ok()
+    }
+    
+    #[inline(always)]
+    pub(crate) fn unreserve(&self) {
+        self.reserved.store(0, Ordering::Release);
+    }
+    
+    /// Address of the lock word, identifies the lock in debug checks and observer events
+    #[inline(always)]
+    pub(crate) fn addr(&self) -> usize {
+        &self.lock as *const AtomicUsize as usize
+    }
+}
+
+impl Default for RawBitwiseRwLock
+{
+    fn default() -> RawBitwiseRwLock {
+        RawBitwiseRwLock::new()
+    }
+}
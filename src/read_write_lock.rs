@@ -0,0 +1,143 @@
+use core::ops::{Deref, DerefMut};
+
+use std::sync;
+use spin;
+
+use rw_lock::{RwLock, ReadLockGuard, WriteLockGuard};
+
+/// Read / write operations shared by the lock implementations, so data structures and benches
+/// can be generic over the lock
+///
+/// # Description
+///
+/// `RwLock` picks a free reader slot itself (see `RwLock::read_any`), so no reader index is
+/// passed here. `std::sync::RwLock` and `spin::RwLock` are covered by `StdRwLock` and
+/// `SpinRwLock`.
+pub trait ReadWriteLock
+{
+    type Target: ? Sized;
+    type ReadGuard<'a>: Deref<Target = Self::Target> where Self: 'a;
+    type WriteGuard<'a>: DerefMut<Target = Self::Target> where Self: 'a;
+    
+    /// Obtain the lock in read mode
+    fn read<'a>(&'a self) -> Self::ReadGuard<'a>;
+    
+    /// Obtain the lock in read mode if that does not require waiting
+    fn try_read<'a>(&'a self) -> Option<Self::ReadGuard<'a>>;
+    
+    /// Obtain the lock in write mode
+    fn write<'a>(&'a self) -> Self::WriteGuard<'a>;
+    
+    /// Obtain the lock in write mode if that does not require waiting
+    fn try_write<'a>(&'a self) -> Option<Self::WriteGuard<'a>>;
+}
+
+/// `std::sync::RwLock` behind `ReadWriteLock`. Poisoning is ignored, the guard is handed out
+/// either way.
+pub struct StdRwLock<T: ? Sized>
+{
+    lock: sync::RwLock<T>,
+}
+
+/// `spin::RwLock` behind `ReadWriteLock`
+pub struct SpinRwLock<T: ? Sized>
+{
+    lock: spin::RwLock<T>,
+}
+
+impl<T> StdRwLock<T>
+{
+    pub fn new(user_data: T) -> StdRwLock<T>
+    {
+        StdRwLock {
+            lock: sync::RwLock::new(user_data),
+        }
+    }
+}
+
+impl<T> SpinRwLock<T>
+{
+    pub fn new(user_data: T) -> SpinRwLock<T>
+    {
+        SpinRwLock {
+            lock: spin::RwLock::new(user_data),
+        }
+    }
+}
+
+impl<T: ? Sized> ReadWriteLock for RwLock<T>
+{
+    type Target = T;
+    type ReadGuard<'a> = ReadLockGuard<'a, T> where T: 'a;
+    type WriteGuard<'a> = WriteLockGuard<'a, T> where T: 'a;
+    
+    fn read<'a>(&'a self) -> ReadLockGuard<'a, T> {
+        self.read_any()
+    }
+    
+    fn try_read<'a>(&'a self) -> Option<ReadLockGuard<'a, T>> {
+        self.try_read_any()
+    }
+    
+    fn write<'a>(&'a self) -> WriteLockGuard<'a, T> {
+        RwLock::write(self)
+    }
+    
+    fn try_write<'a>(&'a self) -> Option<WriteLockGuard<'a, T>> {
+        RwLock::try_write(self)
+    }
+}
+
+impl<T: ? Sized> ReadWriteLock for StdRwLock<T>
+{
+    type Target = T;
+    type ReadGuard<'a> = sync::RwLockReadGuard<'a, T> where T: 'a;
+    type WriteGuard<'a> = sync::RwLockWriteGuard<'a, T> where T: 'a;
+    
+    fn read<'a>(&'a self) -> sync::RwLockReadGuard<'a, T> {
+        self.lock.read().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+    
+    fn try_read<'a>(&'a self) -> Option<sync::RwLockReadGuard<'a, T>> {
+        match self.lock.try_read() {
+            Ok(guard) => Some(guard),
+            Err(sync::TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(sync::TryLockError::WouldBlock) => None,
+        }
+    }
+    
+    fn write<'a>(&'a self) -> sync::RwLockWriteGuard<'a, T> {
+        self.lock.write().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+    
+    fn try_write<'a>(&'a self) -> Option<sync::RwLockWriteGuard<'a, T>> {
+        match self.lock.try_write() {
+            Ok(guard) => Some(guard),
+            Err(sync::TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(sync::TryLockError::WouldBlock) => None,
+        }
+    }
+}
+
+impl<T: ? Sized> ReadWriteLock for SpinRwLock<T>
+{
+    type Target = T;
+    type ReadGuard<'a> = spin::RwLockReadGuard<'a, T> where T: 'a;
+    type WriteGuard<'a> = spin::RwLockWriteGuard<'a, T> where T: 'a;
+    
+    fn read<'a>(&'a self) -> spin::RwLockReadGuard<'a, T> {
+        self.lock.read()
+    }
+    
+    fn try_read<'a>(&'a self) -> Option<spin::RwLockReadGuard<'a, T>> {
+        self.lock.try_read()
+    }
+    
+    fn write<'a>(&'a self) -> spin::RwLockWriteGuard<'a, T> {
+        self.lock.write()
+    }
+    
+    fn try_write<'a>(&'a self) -> Option<spin::RwLockWriteGuard<'a, T>> {
+        self.lock.try_write()
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::current;
+
+use arch::ARCH;
+
+/// Picks the reader index a thread passes to `RwLock::read` and `RwLock::lock_many`
+///
+/// # Description
+///
+/// Two readers that end up with the same index on the same lock are serialized, so a good
+/// strategy keeps concurrently running readers on distinct indices.
+pub trait ReaderIdxStrategy
+{
+    /// Returns an index below `ARCH.reader_cnt`
+    fn reader_idx(&self) -> usize;
+}
+
+thread_local!(static THREAD_ID_HASH: usize = {
+    let mut hasher = DefaultHasher::new();
+    current().id().hash(&mut hasher);
+    hasher.finish() as usize
+});
+
+/// Hash of the current thread id, computed once per thread. Stable for the lifetime of a thread.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadIdHash;
+
+impl ReaderIdxStrategy for ThreadIdHash
+{
+    fn reader_idx(&self) -> usize {
+        THREAD_ID_HASH.with(|hash| *hash % ARCH.reader_cnt)
+    }
+}
+
+/// Hands out the indices one after another
+#[derive(Debug, Default)]
+pub struct RoundRobin
+{
+    next: AtomicUsize,
+}
+
+impl RoundRobin
+{
+    pub fn new() -> RoundRobin {
+        RoundRobin {
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ReaderIdxStrategy for RoundRobin
+{
+    fn reader_idx(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % ARCH.reader_cnt
+    }
+}
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn sched_getcpu() -> i32;
+}
+
+/// Index of the CPU the thread is running on. Threads that run at the same time run on
+/// different CPUs, so there are fewer collisions than with a hash of the thread id. They are
+/// not ruled out: a thread may be preempted or migrated while it holds the slot, and the next
+/// thread scheduled on that CPU then waits on the same idx.
+///
+/// Falls back to `ThreadIdHash` where the CPU id is not available.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CurrentCpu;
+
+impl ReaderIdxStrategy for CurrentCpu
+{
+    #[cfg(target_os = "linux")]
+    fn reader_idx(&self) -> usize {
+        let cpu = unsafe { sched_getcpu() };
+        
+        if cpu < 0 {
+            ThreadIdHash.reader_idx()
+        } else {
+            cpu as usize % ARCH.reader_cnt
+        }
+    }
+    
+    #[cfg(not(target_os = "linux"))]
+    fn reader_idx(&self) -> usize {
+        ThreadIdHash.reader_idx()
+    }
+}
+
+/// Deterministic pseudo-random indices (splitmix64) for reproducible tests. The same seed
+/// yields the same sequence of indices, given the same order of calls.
+#[derive(Debug)]
+pub struct SeededRandom
+{
+    state: AtomicUsize,
+}
+
+const SPLITMIX_GAMMA: u64 = 0x9E3779B97F4A7C15;
+
+impl SeededRandom
+{
+    pub fn new(seed: usize) -> SeededRandom {
+        SeededRandom {
+            state: AtomicUsize::new(seed),
+        }
+    }
+}
+
+impl ReaderIdxStrategy for SeededRandom
+{
+    fn reader_idx(&self) -> usize {
+        let step = self.state.fetch_add(1, Ordering::Relaxed) as u64;
+        
+        let mut z = step.wrapping_add(1).wrapping_mul(SPLITMIX_GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z = z ^ (z >> 31);
+        
+        (z % ARCH.reader_cnt as u64) as usize
+    }
+}
+
+/// Calls `rand::random()` on every use, same as `random_reader_idx`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Random;
+
+impl ReaderIdxStrategy for Random
+{
+    fn reader_idx(&self) -> usize {
+        ::helpers::random_reader_idx()
+    }
+}
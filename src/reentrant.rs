@@ -0,0 +1,258 @@
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::ops::{Drop, Deref, DerefMut};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use util::cpu_relax;
+use helpers::*;
+use arch::ARCH;
+
+thread_local!(static THREAD_MARKER: u8 = const { 0 });
+
+/// Non-zero number that identifies the current thread among the running ones
+#[inline(always)]
+fn current_thread() -> usize {
+    THREAD_MARKER.with(|marker| marker as *const u8 as usize)
+}
+
+/// Provides single-writer multiple-reader lock where the writer may lock it again
+///
+/// # Description
+///
+/// The thread holding the writer bit is recorded next to the lock word, together with a
+/// recursion depth. Nested `write()` and `read(idx)` calls from that thread succeed
+/// immediately, the lock is released once the outermost guard is dropped.
+///
+/// The guards only give shared access. The outermost write guard hands out mutable access
+/// through `borrow_mut()` while no nested guards are alive, and nested `read` / `write` calls
+/// panic for as long as that borrow lasts.
+pub struct ReentrantRwLock<T: ? Sized>
+{
+    lock: AtomicUsize,
+    owner: AtomicUsize,
+    /// Only ever touched by the owner
+    depth: UnsafeCell<usize>,
+    /// Set while a `ReentrantRefMut` is alive, only ever touched by the owner
+    borrowed: UnsafeCell<bool>,
+    data: UnsafeCell<T>,
+}
+
+pub struct ReentrantReadLockGuard<'a, T: ? Sized + 'a>
+{
+    lock: &'a ReentrantRwLock<T>,
+    /// `None` if the guard is nested into a write guard of the same thread
+    idx: Option<usize>,
+    _not_send: PhantomData<*const ()>,
+}
+
+pub struct ReentrantWriteLockGuard<'a, T: ? Sized + 'a>
+{
+    lock: &'a ReentrantRwLock<T>,
+    nested: bool,
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Mutable access to the data of a `ReentrantRwLock`, see `ReentrantWriteLockGuard::borrow_mut`
+pub struct ReentrantRefMut<'g, T: ? Sized + 'g>
+{
+    lock: &'g ReentrantRwLock<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+unsafe impl<T: ? Sized + Send + Sync> Sync for ReentrantRwLock<T> {}
+
+unsafe impl<T: ? Sized + Send> Send for ReentrantRwLock<T> {}
+
+impl<T> ReentrantRwLock<T>
+{
+    pub fn new(user_data: T) -> ReentrantRwLock<T>
+    {
+        ReentrantRwLock {
+            lock: AtomicUsize::new(0),
+            owner: AtomicUsize::new(0),
+            depth: UnsafeCell::new(0),
+            borrowed: UnsafeCell::new(false),
+            data: UnsafeCell::new(user_data),
+        }
+    }
+}
+
+impl<T: ? Sized> ReentrantRwLock<T>
+{
+    #[inline(always)]
+    fn enter(&self) {
+        unsafe {
+            assert!(!*self.borrowed.get(), "Can not enter ReentrantRwLock again while its data is borrowed through borrow_mut()");
+            *self.depth.get() += 1;
+        }
+    }
+    
+    #[inline(always)]
+    fn leave(&self) {
+        let depth = unsafe {
+            *self.depth.get() -= 1;
+            *self.depth.get()
+        };
+        
+        if depth == 0 {
+            self.owner.store(0, Ordering::Relaxed);
+            atomic_writer_unlock(&self.lock);
+        }
+    }
+    
+    /// Returns true if the current thread holds the lock in write mode
+    ///
+    /// Only the owner itself ever stores its own id, so a relaxed load is enough.
+    #[inline(always)]
+    pub fn is_owned_by_current_thread(&self) -> bool {
+        self.owner.load(Ordering::Relaxed) == current_thread()
+    }
+    
+    /// Obtain the lock in read mode
+    ///
+    /// # Arguments
+    /// * `idx` - reader index, unused if the current thread already holds the lock in write mode
+    ///
+    pub fn read(&self, idx: usize) -> ReentrantReadLockGuard<'_, T>
+    {
+        if self.is_owned_by_current_thread() {
+            self.enter();
+            
+            return ReentrantReadLockGuard {
+                lock: self,
+                idx: None,
+                _not_send: PhantomData,
+            };
+        }
+        
+        let idx = idx % ARCH.reader_cnt;
+        
+        loop {
+            let (_, owned, block) = atomic_reader_lock(&self.lock, idx);
+            if owned && !block {
+                break
+            } else if owned {
+                atomic_reader_unlock(&self.lock, idx);
+                cpu_relax();
+            } else {
+                cpu_relax();
+            }
+        }
+        
+        ReentrantReadLockGuard {
+            lock: self,
+            idx: Some(idx),
+            _not_send: PhantomData,
+        }
+    }
+    
+    /// Obtain the lock in write mode, or enter it once more if the current thread already holds it
+    pub fn write(&self) -> ReentrantWriteLockGuard<'_, T>
+    {
+        if self.is_owned_by_current_thread() {
+            self.enter();
+            
+            return ReentrantWriteLockGuard {
+                lock: self,
+                nested: true,
+                _not_send: PhantomData,
+            };
+        }
+        
+        loop {
+            let (_, owned, block) = atomic_writer_lock(&self.lock);
+            
+            if owned && !block {
+                break;
+            } else {
+                cpu_relax();
+            }
+        }
+        
+        self.owner.store(current_thread(), Ordering::Relaxed);
+        self.enter();
+        
+        ReentrantWriteLockGuard {
+            lock: self,
+            nested: false,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ? Sized> ReentrantWriteLockGuard<'a, T>
+{
+    /// Returns true if the guard was obtained while the thread already held the lock
+    pub fn is_nested(&self) -> bool {
+        self.nested
+    }
+    
+    /// Mutable access to the data. Nested `read` / `write` calls of the current thread panic
+    /// until it is dropped.
+    ///
+    /// # Panics
+    ///
+    /// If the guard is nested, or if nested guards of the same lock are alive.
+    pub fn borrow_mut<'g>(&'g mut self) -> ReentrantRefMut<'g, T> {
+        assert!(!self.nested, "A nested write guard of ReentrantRwLock only gives shared access");
+        assert!(unsafe { *self.lock.depth.get() } == 1, "Can not mutate the data while nested guards of ReentrantRwLock are alive");
+        
+        unsafe { *self.lock.borrowed.get() = true; }
+        
+        ReentrantRefMut {
+            lock: self.lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ? Sized> Deref for ReentrantReadLockGuard<'a, T>
+{
+    type Target = T;
+    fn deref(&self) -> &T { unsafe { &*self.lock.data.get() } }
+}
+
+impl<'a, T: ? Sized> Deref for ReentrantWriteLockGuard<'a, T>
+{
+    type Target = T;
+    fn deref(&self) -> &T { unsafe { &*self.lock.data.get() } }
+}
+
+impl<'g, T: ? Sized> Deref for ReentrantRefMut<'g, T>
+{
+    type Target = T;
+    fn deref(&self) -> &T { unsafe { &*self.lock.data.get() } }
+}
+
+impl<'g, T: ? Sized> DerefMut for ReentrantRefMut<'g, T>
+{
+    fn deref_mut(&mut self) -> &mut T { unsafe { &mut *self.lock.data.get() } }
+}
+
+impl<'g, T: ? Sized> Drop for ReentrantRefMut<'g, T>
+{
+    fn drop(&mut self)
+    {
+        unsafe { *self.lock.borrowed.get() = false; }
+    }
+}
+
+impl<'a, T: ? Sized> Drop for ReentrantReadLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        match self.idx {
+            Some(idx) => { atomic_reader_unlock(&self.lock.lock, idx); }
+            None => self.lock.leave(),
+        }
+    }
+}
+
+impl<'a, T: ? Sized> Drop for ReentrantWriteLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.leave();
+    }
+}
@@ -1,10 +1,19 @@
 use core::cell::UnsafeCell;
+use core::mem;
+use core::ptr;
 use core::ops::{Drop, Deref, DerefMut};
 
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{fence, Ordering};
+#[cfg(feature = "observer")]
+use std::sync::Arc;
 
 use util::cpu_relax;
 use helpers::*;
+use debug::*;
+use stats::*;
+use observer::*;
+use state::LockState;
+use raw::RawBitwiseRwLock;
 use arch::ARCH;
 
 /// Provides single-writer multiple-reader lock based on a single atomic primitive
@@ -13,191 +22,161 @@ use arch::ARCH;
 ///
 pub struct RwLock<T: ? Sized>
 {
-    lock: AtomicUsize,
+    pub(crate) raw: RawBitwiseRwLock,
+    /// Empty unless the `stats` feature is enabled
+    stats: LockStats,
+    /// Empty unless the `observer` feature is enabled
+    pub(crate) observer: Observer,
     data: UnsafeCell<T>,
 }
 
+/// Snapshot of the writer version taken by `RwLock::try_optimistic_read`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stamp
+{
+    version: usize,
+    valid: bool,
+}
+
+impl Stamp
+{
+    /// Whether a writer was already holding the lock when the stamp was taken
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+}
+
+/// Not `Send` with the `debug_checks` feature
 pub struct ReadLockGuard<'a, T: ? Sized + 'a>
 {
-    lock: &'a AtomicUsize,
+    lock: &'a RwLock<T>,
+    /// Set if the guard is counted in the overflow counter instead of holding the slot `idx`
+    overflow: bool,
     data: &'a T,
-    pub idx: usize,
+    idx: usize,
+    hold: HoldTimer<'a>,
+    release: ReleaseHook<'a>,
+    _debug: DebugMarker,
 }
 
+/// Not `Send` with the `debug_checks` feature
 pub struct WriteLockGuard<'a, T: ? Sized + 'a>
 {
-    lock: &'a AtomicUsize,
+    lock: &'a RwLock<T>,
     data: &'a mut T,
-    idx: usize,
+    hold: HoldTimer<'a>,
+    release: ReleaseHook<'a>,
+    _debug: DebugMarker,
+}
+
+/// Forgets a guard without releasing the lock, ending the hold time and release event that
+/// `fields` picks out of it
+fn disarm<'a, G, F>(guard: G, fields: F)
+    where F: FnOnce(&G) -> (&HoldTimer<'a>, &ReleaseHook<'a>)
+{
+    // Both are dropped on return
+    let (_hold, _release) = {
+        let (hold, release) = fields(&guard);
+        unsafe { (ptr::read(hold), ptr::read(release)) }
+    };
+    
+    mem::forget(guard);
 }
 
 unsafe impl<T: ? Sized + Send> Sync for RwLock<T> {}
 
 unsafe impl<T: ? Sized + Send> Send for RwLock<T> {}
 
-pub struct LockMany<'a, T: ? Sized + 'a> {
-    pub read: Vec<ReadLockGuard<'a, T>>,
-    pub write: Vec<WriteLockGuard<'a, T>>,
-}
-
 impl<T> RwLock<T>
 {
     pub fn new(user_data: T) -> RwLock<T>
     {
         RwLock {
-            lock: AtomicUsize::new(0),
+            raw: RawBitwiseRwLock::new(),
+            stats: LockStats::new(),
+            observer: Observer::new(),
             data: UnsafeCell::new(user_data),
         }
     }
     
-    /// Locks all readers and writers at once. It's your responsibility that readers and writers
-    /// do not overlap.
-    ///
-    /// # Arguments
-    ///
-    /// * `reader_idx` - an id for the readers (see examples)
-    /// * `read` - a set of locks to be locked in reading mode
-    /// * `write` - a set of locks to be lock in writing mode
-    ///
-    ///
-    pub fn lock_many<'a>(reader_idx: usize, read: &Vec<&'a Self>, write: &Vec<&'a Self>) -> LockMany<'a, T> {
-        // TODO: check if idx is < ARCH.reader_cnt
-        
-        let reader_idx = reader_idx % ARCH.reader_cnt;
-        
-        let mut read_locks = Vec::<&'a Self>::with_capacity(read.len());
-        let mut write_locks = Vec::<&'a Self>::with_capacity(write.len());
-        
-        'root: for i in 0.. {
-            if i > 0 {
-                {
-                    let read_locks = &mut read_locks;
-                    let write_locks = &mut write_locks;
-                    
-                    for &mut r in read_locks {
-                        atomic_reader_unlock(&r.lock, reader_idx);
-                    }
-                    
-                    for &mut w in write_locks {
-                        atomic_writer_unlock(&w.lock);
-                    }
-                }
-                {
-                    let read_locks = &mut read_locks;
-                    let write_locks = &mut write_locks;
-                    (*read_locks).clear();
-                    (*write_locks).clear();
-                }
-                // TODO: We may want to increase the wait time here depending on the location.
-                
-                // TODO: this is a very picky one. 2-thread programs may sync quite well
-                // TODO: and reduce the performance by 10-x.
-                for y in 0..((read.len() + write.len()) * (reader_idx) * 10) {
-                    cpu_relax();
-                }
-            }
-            {
-                let read_locks = &mut read_locks;
-                let write_locks = &mut write_locks;
-                
-                for r in read {
-                    let (_, owned, block) = atomic_reader_lock(&r.lock, reader_idx);
-                    
-                    if owned && block {
-                        atomic_reader_unlock(&r.lock, reader_idx);
-                        continue 'root
-                    } else if !owned {
-                        continue 'root
-                    } else {
-                        (*read_locks).push(r);
-                    }
-                }
-                
-                for w in write {
-                    let (_, owned, block) = atomic_writer_lock(&w.lock);
-                    if block {
-//                        atomic_writer_unlock(&w.lock);
-                        continue 'root;
-                    } else if !owned {
-                        continue 'root
-                    } else {
-                        (*write_locks).push(w);
-                    }
-                }
-            }
-            
-            break;
-        }
-        
-        LockMany::<'a, T> {
-            read: read_locks.iter().map(
-                |args| {
-                    let x = *args;
-                    x.obtained_read(reader_idx)
-                }).collect(),
-            write: write_locks.iter().map(
-                |args| {
-                    let x = *args;
-                    x.obtained_write(ARCH.reader_cnt)
-                }).collect()
+    /// Creates a lock reporting its events to `observer`, in addition to the global observer
+    #[cfg(feature = "observer")]
+    pub fn with_observer<O: LockObserver + Send + Sync + 'static>(user_data: T, observer: O) -> RwLock<T>
+    {
+        RwLock {
+            raw: RawBitwiseRwLock::new(),
+            stats: LockStats::new(),
+            observer: Observer::with_observer(Arc::new(observer)),
+            data: UnsafeCell::new(user_data),
         }
     }
+
 }
 
 impl<T: ? Sized> RwLock<T>
 {
     #[inline(always)]
     fn obtain_reader_lock(&self, idx: usize) -> usize {
-        // TODO: check if idx is < ARCH.reader_cnt
+        let spins = self.raw.obtain_shared(idx);
         
-        'root: loop {
-            let (_, owned, block) = atomic_reader_lock(&self.lock, idx);
-            if owned && !block {
-                cpu_relax();
-                break
-            } else if owned {
-                atomic_reader_unlock(&self.lock, idx);
-                cpu_relax();
-            } else {
-                cpu_relax();
-            }
-        }
+        self.report_acquired(LockKind::Read, idx, spins);
         
-        return idx;
+        idx
     }
     
     #[inline(always)]
     fn obtain_writer_lock(&self) -> usize {
         let idx = ARCH.reader_cnt;
+        let spins = self.raw.obtain_exclusive();
         
-        'root: loop {
-            let (_, owned, block) = atomic_writer_lock(&self.lock);
-            
-            if owned && !block {
-                break;
-            } else {
-                cpu_relax();
-            }
-        }
+        self.report_acquired(LockKind::Write, idx, spins);
         
         idx
     }
     
-    fn obtained_read(&self, idx: usize) -> ReadLockGuard<T> {
+    #[inline(always)]
+    pub(crate) fn addr(&self) -> usize {
+        self.raw.addr()
+    }
+    
+    /// Reports a successful acquisition to the stats and the observers
+    #[inline(always)]
+    pub(crate) fn report_acquired(&self, kind: LockKind, idx: usize, spins: usize) {
+        self.stats.acquired(spins);
+        self.observer.acquired(self.addr(), kind, idx, spins);
+    }
+    
+    #[inline(always)]
+    pub(crate) fn report_lock_many_retry(&self, retry: usize) {
+        self.stats.lock_many_retry();
+        self.observer.lock_many_retry(self.addr(), retry);
+    }
+    
+    pub(crate) fn obtained_read(&self, idx: usize) -> ReadLockGuard<'_, T> {
+        debug_acquired(&self.raw.lock, Held::Read(idx));
+        
         ReadLockGuard {
-            idx: idx,
-            lock: &self.lock,
+            idx,
+            lock: self,
+            overflow: false,
             data: unsafe { &mut *self.data.get() },
             //                        data: &self.data,
+            hold: self.stats.hold_timer(),
+            release: self.observer.release_hook(self.addr(), LockKind::Read, idx),
+            _debug: DebugMarker::default(),
         }
     }
     
-    fn obtained_write(&self, idx: usize) -> WriteLockGuard<T> {
+    pub(crate) fn obtained_write(&self, idx: usize) -> WriteLockGuard<'_, T> {
+        debug_acquired(&self.raw.lock, Held::Write);
+        
         WriteLockGuard {
-            idx: idx,
-            lock: &self.lock,
+            lock: self,
             data: unsafe { &mut *self.data.get() },
             //                        data: &self.data,
+            hold: self.stats.hold_timer(),
+            release: self.observer.release_hook(self.addr(), LockKind::Write, idx),
+            _debug: DebugMarker::default(),
         }
     }
     
@@ -206,54 +185,345 @@ impl<T: ? Sized> RwLock<T>
     /// # Arguments
     /// * `idx` - reader index
     ///
-    pub fn read(&self, idx: usize) -> ReadLockGuard<T>
+    pub fn read(&self, idx: usize) -> ReadLockGuard<'_, T>
     {
+        debug_check(&self.raw.lock, Held::Read(idx));
+        self.observer.acquire_start(self.addr(), LockKind::Read, idx);
+        
         self.obtained_read(self.obtain_reader_lock(idx))
     }
     
+    /// Obtain the lock in read mode through whichever reader slot is free
+    ///
+    /// # Description
+    ///
+    /// The slot is picked from the lock word itself, so there is no need to come up with a
+    /// reader index. The guard's `idx()` is the slot it ended up with.
+    pub fn read_any(&self) -> ReadLockGuard<'_, T>
+    {
+        debug_check(&self.raw.lock, Held::Shared);
+        self.observer.acquire_start(self.addr(), LockKind::Read, ARCH.reader_cnt);
+        
+        let mut spins = 0;
+        
+        loop {
+            if let Some(idx) = atomic_reader_lock_any(&self.raw.lock) {
+                self.report_acquired(LockKind::Read, idx, spins);
+                
+                return self.obtained_read(idx);
+            }
+            
+            spins += 1;
+            cpu_relax();
+        }
+    }
+    
+    /// Makes a single attempt at `read_any`. Returns `None` if a writer holds the lock or every
+    /// reader slot is taken.
+    pub fn try_read_any(&self) -> Option<ReadLockGuard<'_, T>>
+    {
+        self.observer.acquire_start(self.addr(), LockKind::Read, ARCH.reader_cnt);
+        
+        atomic_reader_lock_any(&self.raw.lock).map(|idx| {
+            self.report_acquired(LockKind::Read, idx, 0);
+            self.obtained_read(idx)
+        })
+    }
+    
+    /// Obtain the lock in read mode, falling back to the shared overflow counter if the slot
+    /// `idx` is held by someone else
+    ///
+    /// # Description
+    ///
+    /// Use it when there are more concurrent readers than `ARCH.reader_cnt`. Readers that
+    /// collide on a slot are then counted instead of serialized on it.
+    ///
+    /// # Arguments
+    /// * `idx` - preferred reader index
+    ///
+    pub fn read_or_overflow(&self, idx: usize) -> ReadLockGuard<'_, T>
+    {
+        debug_check(&self.raw.lock, Held::Shared);
+        self.observer.acquire_start(self.addr(), LockKind::Read, idx);
+        
+        let mut spins = 0;
+        
+        loop {
+            let (_, owned, block) = atomic_reader_lock(&self.raw.lock, idx);
+            
+            if owned && !block {
+                self.report_acquired(LockKind::Read, idx, spins);
+                
+                return self.obtained_read(idx);
+            } else if owned {
+                atomic_reader_unlock(&self.raw.lock, idx);
+            } else if atomic_overflow_lock(&self.raw.lock, &self.raw.overflow) {
+                self.report_acquired(LockKind::Overflow, idx, spins);
+                debug_acquired(&self.raw.lock, Held::Shared);
+                
+                return ReadLockGuard {
+                    idx,
+                    lock: self,
+                    overflow: true,
+                    data: unsafe { &*self.data.get() },
+                    hold: self.stats.hold_timer(),
+                    release: self.observer.release_hook(self.addr(), LockKind::Overflow, idx),
+                    _debug: DebugMarker::default(),
+                };
+            }
+            
+            spins += 1;
+            cpu_relax();
+        }
+    }
+    
     /// Obtain the lock in write mode
-    pub fn write(&self) -> WriteLockGuard<T>
+    pub fn write(&self) -> WriteLockGuard<'_, T>
     {
+        debug_check(&self.raw.lock, Held::Write);
+        self.observer.acquire_start(self.addr(), LockKind::Write, ARCH.reader_cnt);
+        
         self.obtained_write(self.obtain_writer_lock())
     }
+    
+    /// Makes a single attempt at `write()`
+    pub fn try_write(&self) -> Option<WriteLockGuard<'_, T>>
+    {
+        debug_check(&self.raw.lock, Held::Write);
+        self.observer.acquire_start(self.addr(), LockKind::Write, ARCH.reader_cnt);
+        
+        if self.raw.try_lock_exclusive() {
+            self.report_acquired(LockKind::Write, ARCH.reader_cnt, 0);
+            
+            Some(self.obtained_write(ARCH.reader_cnt))
+        } else {
+            None
+        }
+    }
+    
+    /// Take a stamp for reading the data without setting a reader bit
+    ///
+    /// # Description
+    ///
+    /// Data may then be copied out through `data_ptr()` and must only be used after
+    /// `validate(stamp)` has returned `true`. Otherwise fall back to `read(idx)`.
+    ///
+    /// The stamp is invalid from the start if a writer is currently holding the lock.
+    pub fn try_optimistic_read(&self) -> Stamp
+    {
+        let version = self.raw.version.load(Ordering::Acquire);
+        let state = self.raw.lock.load(Ordering::Acquire);
+        
+        Stamp {
+            version,
+            valid: state & bitmask_lock(ARCH.reader_cnt) == 0,
+        }
+    }
+    
+    /// Returns true if no writer has obtained the lock since the stamp was taken
+    pub fn validate(&self, stamp: Stamp) -> bool
+    {
+        fence(Ordering::Acquire);
+        
+        stamp.valid && self.raw.version.load(Ordering::Relaxed) == stamp.version
+    }
+    
+    /// Decodes the current state of the lock
+    pub fn state(&self) -> LockState
+    {
+        self.raw.state()
+    }
+    
+    /// Returns true if the lock is held in any mode
+    pub fn is_locked(&self) -> bool
+    {
+        self.raw.is_locked()
+    }
+    
+    /// Returns true if the lock is held in write mode
+    pub fn is_write_locked(&self) -> bool
+    {
+        self.raw.is_write_locked()
+    }
+    
+    /// Amount of readers currently holding the lock
+    pub fn reader_count(&self) -> usize
+    {
+        self.raw.reader_count()
+    }
+    
+    /// Releases a read lock whose guard has been leaked, see `ReadLockGuard::leak`
+    ///
+    /// With `debug_checks`, call it on the thread that obtained the lock.
+    ///
+    /// # Safety
+    ///
+    /// The reader bit `idx` must be held, and the data must no longer be accessed through the
+    /// leaked reference.
+    pub unsafe fn force_unlock_read(&self, idx: usize)
+    {
+        debug_released(&self.raw.lock, Held::Read(idx));
+        atomic_unlock(&self.raw.lock, idx);
+        let _hook = self.observer.release_hook(self.addr(), LockKind::Read, idx);
+    }
+    
+    /// Releases a write lock whose guard has been leaked, see `WriteLockGuard::into_raw`
+    ///
+    /// With `debug_checks`, call it on the thread that obtained the lock.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held in write mode, and the data must no longer be accessed through
+    /// the pointer obtained from `into_raw`.
+    pub unsafe fn force_unlock_write(&self)
+    {
+        debug_released(&self.raw.lock, Held::Write);
+        atomic_unlock(&self.raw.lock, ARCH.reader_cnt);
+        let _hook = self.observer.release_hook(self.addr(), LockKind::Write, ARCH.reader_cnt);
+    }
+    
+    /// The lock word underneath, without the data
+    pub fn raw(&self) -> &RawBitwiseRwLock
+    {
+        &self.raw
+    }
+    
+    /// Contention counters of this lock
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &LockStats
+    {
+        &self.stats
+    }
+    
+    /// Raw pointer to the data, for optimistic reads only
+    pub fn data_ptr(&self) -> *const T
+    {
+        self.data.get()
+    }
 }
 
-macro_rules! define_deref_for {
-    ($cls:path) => (
-        impl<'a, T: ? Sized> Deref for ($cls)
-        {
-            type Target = T;
-            fn deref<'b>(&'b self) -> &'b T { &*self.data }
+impl<'a, T: ? Sized> ReadLockGuard<'a, T>
+{
+    /// The reader slot the guard holds, or asked for if it is counted in the overflow counter
+    pub fn idx(&self) -> usize
+    {
+        self.idx
+    }
+    
+    /// Keeps the lock held past the end of the guard, it is then released with
+    /// `RwLock::force_unlock_read(idx)`. Read `idx()` before leaking the guard.
+    ///
+    /// # Panics
+    ///
+    /// If the guard was obtained through the overflow counter, see `RwLock::read_or_overflow`.
+    pub fn leak(guard: Self) -> &'a T
+    {
+        assert!(!guard.overflow, "Can not leak a ReadLockGuard counted in the overflow counter");
+        
+        let data = guard.data as *const T;
+        mem::forget(guard);
+        
+        unsafe { &*data }
+    }
+    
+    /// Turns the read lock into the write lock if the guard is the only reader, hands the
+    /// guard back otherwise
+    ///
+    /// # Description
+    ///
+    /// Never waits, so it can not deadlock with another reader upgrading at the same time,
+    /// and other locks held meanwhile are never at risk. Guards counted in the overflow counter
+    /// are always handed back, and so are guards on a lock a `lock_many` call has reserved.
+    pub fn try_upgrade(guard: Self) -> Result<WriteLockGuard<'a, T>, Self>
+    {
+        if guard.overflow || guard.lock.raw.reserved_by_other(0) || !unsafe { guard.lock.raw.try_upgrade(guard.idx) } {
+            return Err(guard);
         }
-    )
+        
+        let (lock, idx) = (guard.lock, guard.idx);
+        
+        debug_released(&lock.raw.lock, Held::Read(idx));
+        disarm(guard, |guard| (&guard.hold, &guard.release));
+        
+        lock.observer.acquire_start(lock.addr(), LockKind::Write, ARCH.reader_cnt);
+        lock.report_acquired(LockKind::Write, ARCH.reader_cnt, 0);
+        
+        Ok(lock.obtained_write(ARCH.reader_cnt))
+    }
 }
 
-macro_rules! define_deref_mut_for {
-    ($cls:path) => (
-        impl<'a, T: ? Sized> DerefMut for ($cls)
-        {
-            fn deref_mut<'b>(&'b mut self) -> &'b mut T {
-                &mut *self.data
-            }
+impl<'a, T: ? Sized> WriteLockGuard<'a, T>
+{
+    /// Keeps the lock held past the end of the guard and returns the data pointer. Release it
+    /// with `RwLock::force_unlock_write()`, or turn it back into a guard with `from_raw`.
+    pub fn into_raw(guard: Self) -> *mut T
+    {
+        // Taken from the cell: a pointer cast from a shared reborrow of `data` may not be written
+        // through
+        let data = guard.lock.data.get();
+        mem::forget(guard);
+        
+        data
+    }
+    
+    /// Rebuilds a guard for a lock that is held in write mode, e.g. after `into_raw`
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held in write mode and no other guard or pointer may access the data.
+    pub unsafe fn from_raw(lock: &'a RwLock<T>) -> WriteLockGuard<'a, T>
+    {
+        WriteLockGuard {
+            lock,
+            data: &mut *lock.data.get(),
+            hold: lock.stats.hold_timer(),
+            release: lock.observer.release_hook(lock.addr(), LockKind::Write, ARCH.reader_cnt),
+            _debug: DebugMarker::default(),
         }
-    )
+    }
+    
+    /// Turns the write lock into a read lock on the reader slot `idx`, without letting another
+    /// writer in between
+    pub fn downgrade(guard: Self, idx: usize) -> ReadLockGuard<'a, T>
+    {
+        let lock = guard.lock;
+        let idx = idx % ARCH.reader_cnt;
+        
+        unsafe { lock.raw.downgrade(idx); }
+        
+        debug_released(&lock.raw.lock, Held::Write);
+        disarm(guard, |guard| (&guard.hold, &guard.release));
+        
+        lock.observer.acquire_start(lock.addr(), LockKind::Read, idx);
+        lock.report_acquired(LockKind::Read, idx, 0);
+        
+        lock.obtained_read(idx)
+    }
 }
 
-macro_rules! define_drop_for {
-    ($cls:path) => (
-        impl<'a, T: ? Sized> Drop for ($cls)
-        {
-            /// Can we, when the initialisation is being done
-            fn drop(&mut self)
-            {
-                atomic_unlock(self.lock, self.idx);
-            }
+impl<'a, T: ? Sized> Drop for ReadLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        if self.overflow {
+            debug_released(&self.lock.raw.lock, Held::Shared);
+            atomic_overflow_unlock(&self.lock.raw.overflow);
+        } else {
+            debug_released(&self.lock.raw.lock, Held::Read(self.idx));
+            unsafe { self.lock.raw.unlock_shared(self.idx); }
         }
-    )
+    }
+}
+
+impl<'a, T: ? Sized> Drop for WriteLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        debug_released(&self.lock.raw.lock, Held::Write);
+        unsafe { self.lock.raw.unlock_exclusive(); }
+    }
 }
 
 define_deref_for!(ReadLockGuard<'a, T>);
 define_deref_for!(WriteLockGuard<'a, T>);
-define_deref_mut_for!(WriteLockGuard<'a, T>);
-define_drop_for!(ReadLockGuard<'a, T>);
-define_drop_for!(WriteLockGuard<'a, T>);
+define_deref_mut_for!(WriteLockGuard<'a, T>);
\ No newline at end of file
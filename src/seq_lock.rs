@@ -0,0 +1,139 @@
+use core::cell::UnsafeCell;
+use core::ops::{Drop, Deref, DerefMut};
+use core::ptr;
+
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use util::cpu_relax;
+use helpers::*;
+use arch::ARCH;
+
+/// Provides single-writer lock with optimistic, lock-free readers for `Copy` data
+///
+/// # Description
+///
+/// The writer claims the same writer bit as in `RwLock`, while the reader bits of the word
+/// hold a sequence counter instead. The counter is odd while a writer is active.
+///
+/// Readers never write to the lock word: they copy the data out and retry whenever the
+/// sequence has changed in the meantime.
+pub struct SeqRwLock<T: Copy>
+{
+    lock: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+pub struct SeqWriteLockGuard<'a, T: Copy + 'a>
+{
+    lock: &'a AtomicUsize,
+    data: &'a mut T,
+    seq: usize,
+}
+
+unsafe impl<T: Copy + Send> Sync for SeqRwLock<T> {}
+
+unsafe impl<T: Copy + Send> Send for SeqRwLock<T> {}
+
+#[inline(always)]
+fn seq_next(seq: usize) -> usize {
+    seq.wrapping_add(1) & bitmask_readers_lock()
+}
+
+impl<T: Copy> SeqRwLock<T>
+{
+    pub fn new(user_data: T) -> SeqRwLock<T>
+    {
+        SeqRwLock {
+            lock: AtomicUsize::new(0),
+            data: UnsafeCell::new(user_data),
+        }
+    }
+    
+    /// Current value of the sequence counter
+    pub fn seq(&self) -> usize {
+        atomic_load(&self.lock) & bitmask_readers_lock()
+    }
+    
+    /// Makes a single attempt at copying the data out, returns `None` if a writer interfered.
+    pub fn try_read(&self) -> Option<T>
+    {
+        let before = self.lock.load(Ordering::Acquire);
+        
+        if before & bitmask_lock(ARCH.reader_cnt) != 0 || before & 1 != 0 {
+            return None;
+        }
+        
+        let data = unsafe { ptr::read_volatile(self.data.get()) };
+        
+        fence(Ordering::Acquire);
+        
+        if atomic_load(&self.lock) == before {
+            Some(data)
+        } else {
+            None
+        }
+    }
+    
+    /// Obtain a consistent copy of the data, spinning while writers interfere
+    pub fn read(&self) -> T
+    {
+        loop {
+            match self.try_read() {
+                Some(data) => return data,
+                None => cpu_relax(),
+            }
+        }
+    }
+    
+    #[inline(always)]
+    fn obtain_writer_lock(&self) -> usize {
+        loop {
+            let state = atomic_load(&self.lock);
+            
+            if state & bitmask_lock(ARCH.reader_cnt) == 0 {
+                let seq = seq_next(state);
+                let next = seq | bitmask_lock(ARCH.reader_cnt);
+                
+                if self.lock.compare_exchange_weak(state, next, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                    fence(Ordering::Release);
+                    return seq;
+                }
+            }
+            
+            cpu_relax();
+        }
+    }
+    
+    /// Obtain the lock in write mode
+    pub fn write(&self) -> SeqWriteLockGuard<'_, T>
+    {
+        let seq = self.obtain_writer_lock();
+        
+        SeqWriteLockGuard {
+            lock: &self.lock,
+            data: unsafe { &mut *self.data.get() },
+            seq,
+        }
+    }
+}
+
+impl<'a, T: Copy> Deref for SeqWriteLockGuard<'a, T>
+{
+    type Target = T;
+    fn deref(&self) -> &T { &*self.data }
+}
+
+impl<'a, T: Copy> DerefMut for SeqWriteLockGuard<'a, T>
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut *self.data
+    }
+}
+
+impl<'a, T: Copy> Drop for SeqWriteLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.store(seq_next(self.seq), Ordering::Release);
+    }
+}
@@ -0,0 +1,192 @@
+use core::cell::UnsafeCell;
+use core::ops::{Drop, Deref, DerefMut};
+
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use util::cpu_relax;
+use helpers::*;
+use arch::ARCH;
+
+/// Amount of shards used by `ShardedRwLock::new`
+pub const DEFAULT_SHARD_CNT: usize = 8;
+
+/// A lock word that occupies a cache line on its own, so that readers of different shards
+/// do not bounce the same line between cores.
+#[repr(align(64))]
+struct Shard
+{
+    lock: AtomicUsize,
+}
+
+/// Provides single-writer multiple-reader lock with the reader bits spread over several
+/// cache-line-padded words.
+///
+/// # Description
+///
+/// Every shard is a complete lock word of `ARCH.reader_cnt` reader bits and a writer bit.
+/// A reader only touches the shard that owns its slot, while a writer claims the writer bit
+/// on every shard and then waits for all of them to drain.
+///
+/// Reader indices range over `0..lock.reader_cnt()`; consecutive indices land on different shards.
+pub struct ShardedRwLock<T: ? Sized>
+{
+    shards: Box<[Shard]>,
+    data: UnsafeCell<T>,
+}
+
+pub struct ShardedReadLockGuard<'a, T: ? Sized + 'a>
+{
+    lock: &'a AtomicUsize,
+    data: &'a T,
+    pub idx: usize,
+}
+
+pub struct ShardedWriteLockGuard<'a, T: ? Sized + 'a>
+{
+    shards: &'a [Shard],
+    data: &'a mut T,
+}
+
+unsafe impl<T: ? Sized + Send + Sync> Sync for ShardedRwLock<T> {}
+
+unsafe impl<T: ? Sized + Send> Send for ShardedRwLock<T> {}
+
+impl<T> ShardedRwLock<T>
+{
+    pub fn new(user_data: T) -> ShardedRwLock<T>
+    {
+        ShardedRwLock::with_shards(user_data, DEFAULT_SHARD_CNT)
+    }
+    
+    /// # Arguments
+    ///
+    /// * `shard_cnt` - amount of lock words to spread the readers over, at least `1`
+    ///
+    pub fn with_shards(user_data: T, shard_cnt: usize) -> ShardedRwLock<T>
+    {
+        assert!(shard_cnt > 0, "ShardedRwLock needs at least one shard");
+        
+        ShardedRwLock {
+            shards: (0..shard_cnt).map(|_| Shard { lock: AtomicUsize::new(0) }).collect::<Vec<_>>().into_boxed_slice(),
+            data: UnsafeCell::new(user_data),
+        }
+    }
+}
+
+impl<T: ? Sized> ShardedRwLock<T>
+{
+    /// Amount of shards backing this lock
+    pub fn shard_cnt(&self) -> usize {
+        self.shards.len()
+    }
+    
+    /// Amount of distinct reader indices this lock supports
+    pub fn reader_cnt(&self) -> usize {
+        self.shards.len() * ARCH.reader_cnt
+    }
+    
+    #[inline(always)]
+    fn shard_of(&self, idx: usize) -> (&AtomicUsize, usize) {
+        let idx = idx % self.reader_cnt();
+        let shard_cnt = self.shards.len();
+        
+        (&self.shards[idx % shard_cnt].lock, idx / shard_cnt)
+    }
+    
+    #[inline(always)]
+    fn obtain_reader_lock(&self, idx: usize) -> (&AtomicUsize, usize) {
+        let (lock, idx) = self.shard_of(idx);
+        
+        loop {
+            let (_, owned, block) = atomic_reader_lock(lock, idx);
+            if owned && !block {
+                break
+            } else if owned {
+                atomic_reader_unlock(lock, idx);
+                cpu_relax();
+            } else {
+                cpu_relax();
+            }
+        }
+        
+        fence(Ordering::Acquire);
+        
+        (lock, idx)
+    }
+    
+    #[inline(always)]
+    fn obtain_writer_lock(&self) {
+        // The writer bit of the first shard serializes the writers.
+        loop {
+            let prev_state = atomic_lock(&self.shards[0].lock, ARCH.reader_cnt);
+            
+            if prev_state & bitmask_lock(ARCH.reader_cnt) == 0 {
+                break
+            } else {
+                cpu_relax();
+            }
+        }
+        
+        for shard in &self.shards[1..] {
+            atomic_lock(&shard.lock, ARCH.reader_cnt);
+        }
+        
+        for shard in self.shards.iter() {
+            while !atomic_readers_free(&shard.lock) {
+                cpu_relax();
+            }
+        }
+        
+        fence(Ordering::Acquire);
+    }
+    
+    /// Obtain the lock in read mode
+    ///
+    /// # Arguments
+    /// * `idx` - reader index, taken modulo `reader_cnt()`
+    ///
+    pub fn read(&self, idx: usize) -> ShardedReadLockGuard<'_, T>
+    {
+        let (lock, idx) = self.obtain_reader_lock(idx);
+        
+        ShardedReadLockGuard {
+            lock,
+            data: unsafe { &*self.data.get() },
+            idx,
+        }
+    }
+    
+    /// Obtain the lock in write mode
+    pub fn write(&self) -> ShardedWriteLockGuard<'_, T>
+    {
+        self.obtain_writer_lock();
+        
+        ShardedWriteLockGuard {
+            shards: &self.shards,
+            data: unsafe { &mut *self.data.get() },
+        }
+    }
+}
+
+impl<'a, T: ? Sized> Drop for ShardedReadLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        atomic_reader_unlock(self.lock, self.idx);
+    }
+}
+
+impl<'a, T: ? Sized> Drop for ShardedWriteLockGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        // Release the first shard last, so the next writer can not start before the rest is clear.
+        for shard in self.shards.iter().rev() {
+            atomic_writer_unlock(&shard.lock);
+        }
+    }
+}
+
+define_deref_for!(ShardedReadLockGuard<'a, T>);
+define_deref_for!(ShardedWriteLockGuard<'a, T>);
+define_deref_mut_for!(ShardedWriteLockGuard<'a, T>);
@@ -0,0 +1,52 @@
+use helpers::*;
+use arch::ARCH;
+
+/// Decoded state of a `RwLock`, see `RwLock::state`
+///
+/// # Description
+///
+/// The lock word is read in a single load, the counters right after it, so the state may
+/// already be outdated by the time it is inspected. Readers that are turned away by a writer
+/// may also show up briefly in `reader_slots`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockState
+{
+    /// Whether the writer bit is set
+    pub writer: bool,
+    /// Bitmask of the reader slots that are set, bit `i` stands for the reader index `i`
+    pub reader_slots: usize,
+    /// Readers counted in the overflow counter, see `RwLock::read_or_overflow`
+    pub overflow_readers: usize,
+    /// Amount of writer acquisitions so far, see `RwLock::try_optimistic_read`
+    pub version: usize,
+    /// Whether a `lock_many` call has reserved the lock, see `LockManyOptions::reserve_after`
+    pub reserved: bool,
+}
+
+impl LockState
+{
+    pub(crate) fn decode(state: usize, overflow_readers: usize, version: usize, reserved: bool) -> LockState {
+        LockState {
+            writer: state_writer_held(state),
+            reader_slots: state_reader_slots(state),
+            overflow_readers,
+            version,
+            reserved,
+        }
+    }
+    
+    /// Total amount of readers, slotted and overflowing
+    pub fn reader_count(&self) -> usize {
+        self.reader_slots.count_ones() as usize + self.overflow_readers
+    }
+    
+    /// Indices of the reader slots that are held, in ascending order
+    pub fn held_slots(&self) -> Vec<usize> {
+        (0..ARCH.reader_cnt).filter(|&idx| self.reader_slots & bitmask_lock(idx) != 0).collect()
+    }
+    
+    /// Whether the lock is held in any mode
+    pub fn is_locked(&self) -> bool {
+        self.writer || self.reader_count() > 0
+    }
+}
@@ -0,0 +1,156 @@
+//! Contention statistics of a single lock, enabled by the `stats` feature.
+//!
+//! Without the feature `LockStats` and `HoldTimer` are empty and every method here is a no-op.
+
+use std::marker::PhantomData;
+use std::time::Duration;
+
+#[cfg(feature = "stats")]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "stats")]
+use std::time::Instant;
+
+/// Counters of a single lock, see `RwLock::stats`
+#[derive(Default, Debug)]
+pub struct LockStats
+{
+    #[cfg(feature = "stats")]
+    acquisitions: AtomicUsize,
+    #[cfg(feature = "stats")]
+    contended: AtomicUsize,
+    #[cfg(feature = "stats")]
+    spins: AtomicUsize,
+    #[cfg(feature = "stats")]
+    lock_many_retries: AtomicUsize,
+    #[cfg(feature = "stats")]
+    total_hold_ns: AtomicU64,
+    #[cfg(feature = "stats")]
+    max_hold_ns: AtomicU64,
+}
+
+/// Values of `LockStats` at a point in time
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct LockStatsSnapshot
+{
+    /// Times the lock was obtained, in either mode
+    pub acquisitions: usize,
+    /// Acquisitions that had to wait at least once
+    pub contended: usize,
+    /// Total amount of failed attempts over all acquisitions
+    pub spins: usize,
+    /// Times a `lock_many` call had to start over because of this lock
+    pub lock_many_retries: usize,
+    pub total_hold: Duration,
+    pub max_hold: Duration,
+}
+
+/// Measures for how long a guard has been alive, part of every guard of a lock with stats
+pub struct HoldTimer<'a>
+{
+    #[cfg(feature = "stats")]
+    stats: &'a LockStats,
+    #[cfg(feature = "stats")]
+    since: Instant,
+    _lifetime: PhantomData<&'a LockStats>,
+}
+
+impl LockStats
+{
+    #[cfg(feature = "stats")]
+    pub(crate) fn new() -> LockStats {
+        LockStats {
+            acquisitions: AtomicUsize::new(0),
+            contended: AtomicUsize::new(0),
+            spins: AtomicUsize::new(0),
+            lock_many_retries: AtomicUsize::new(0),
+            total_hold_ns: AtomicU64::new(0),
+            max_hold_ns: AtomicU64::new(0),
+        }
+    }
+    
+    #[cfg(not(feature = "stats"))]
+    #[inline(always)]
+    pub(crate) fn new() -> LockStats {
+        LockStats {}
+    }
+    
+    /// Current values of the counters
+    #[cfg(feature = "stats")]
+    pub fn snapshot(&self) -> LockStatsSnapshot {
+        LockStatsSnapshot {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            spins: self.spins.load(Ordering::Relaxed),
+            lock_many_retries: self.lock_many_retries.load(Ordering::Relaxed),
+            total_hold: Duration::from_nanos(self.total_hold_ns.load(Ordering::Relaxed)),
+            max_hold: Duration::from_nanos(self.max_hold_ns.load(Ordering::Relaxed)),
+        }
+    }
+    
+    /// Sets all the counters to zero, returns their values from right before
+    #[cfg(feature = "stats")]
+    pub fn reset(&self) -> LockStatsSnapshot {
+        LockStatsSnapshot {
+            acquisitions: self.acquisitions.swap(0, Ordering::Relaxed),
+            contended: self.contended.swap(0, Ordering::Relaxed),
+            spins: self.spins.swap(0, Ordering::Relaxed),
+            lock_many_retries: self.lock_many_retries.swap(0, Ordering::Relaxed),
+            total_hold: Duration::from_nanos(self.total_hold_ns.swap(0, Ordering::Relaxed)),
+            max_hold: Duration::from_nanos(self.max_hold_ns.swap(0, Ordering::Relaxed)),
+        }
+    }
+    
+    /// Records a successful acquisition after `spins` failed attempts
+    #[cfg(feature = "stats")]
+    pub(crate) fn acquired(&self, spins: usize) {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        
+        if spins > 0 {
+            self.contended.fetch_add(1, Ordering::Relaxed);
+            self.spins.fetch_add(spins, Ordering::Relaxed);
+        }
+    }
+    
+    #[cfg(not(feature = "stats"))]
+    #[inline(always)]
+    pub(crate) fn acquired(&self, _spins: usize) {}
+    
+    #[cfg(feature = "stats")]
+    pub(crate) fn lock_many_retry(&self) {
+        self.lock_many_retries.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    #[cfg(not(feature = "stats"))]
+    #[inline(always)]
+    pub(crate) fn lock_many_retry(&self) {}
+    
+    #[cfg(feature = "stats")]
+    pub(crate) fn hold_timer(&self) -> HoldTimer<'_> {
+        HoldTimer {
+            stats: self,
+            since: Instant::now(),
+            _lifetime: PhantomData,
+        }
+    }
+    
+    #[cfg(not(feature = "stats"))]
+    #[inline(always)]
+    pub(crate) fn hold_timer(&self) -> HoldTimer<'_> {
+        HoldTimer {
+            _lifetime: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "stats")]
+impl<'a> Drop for HoldTimer<'a>
+{
+    fn drop(&mut self)
+    {
+        let held = self.since.elapsed();
+        let held = held.as_secs() * 1_000_000_000 + held.subsec_nanos() as u64;
+        
+        self.stats.total_hold_ns.fetch_add(held, Ordering::Relaxed);
+        self.stats.max_hold_ns.fetch_max(held, Ordering::Relaxed);
+    }
+}
@@ -1,15 +1,8 @@
 /// Called while spinning (name borrowed from Linux). Can be implemented to call
 /// a platform-specific method of lightening CPU load in spinlocks.
 /// take from (https://github.com/mvdnes/spin-rs/blob/master/src/util.rs)
-#[cfg(all(feature = "asm", any(target_arch = "x86", target_arch = "x86_64")))]
-#[inline(always)]
-pub fn cpu_relax() {
-    // This instruction is meant for usage in spinlock loops
-    // (see Intel x86 manual, III, 4.2)
-    unsafe { asm!("pause" :::: "volatile"); }
-}
-
-#[cfg(any(not(feature = "asm"), not(any(target_arch = "x86", target_arch = "x86_64"))))]
 #[inline(always)]
 pub fn cpu_relax() {
+    // `pause` on x86 (see Intel x86 manual, III, 4.2), the equivalent hint elsewhere
+    core::hint::spin_loop();
 }
\ No newline at end of file
@@ -0,0 +1,31 @@
+extern crate spin_bitwise;
+
+use spin_bitwise::Backoff;
+
+#[test]
+fn exponential_doubles_up_to_max() {
+    let backoff = Backoff::Exponential { initial: 16, max: 1000 };
+    
+    let spins: Vec<_> = (1..8).map(|retry| backoff.spins(retry, 2, 0)).collect();
+    assert_eq!(spins, vec![16, 32, 64, 128, 256, 512, 1000]);
+}
+
+#[test]
+fn exponential_saturates_instead_of_wrapping() {
+    let large = Backoff::Exponential { initial: 1 << 40, max: usize::MAX };
+    assert_eq!(large.spins(30, 1, 0), usize::MAX);
+    
+    let odd = Backoff::Exponential { initial: 3, max: 5000 };
+    
+    for retry in [63, 64, 65, 1000, usize::MAX] {
+        assert_eq!(odd.spins(retry, 1, 0), 5000);
+    }
+}
+
+#[test]
+fn staggered_moves_with_retry_and_reader_idx() {
+    let spins: Vec<_> = (1..6).map(|retry| Backoff::Staggered.spins(retry, 3, 2)).collect();
+    assert_eq!(spins, vec![90, 0, 30, 60, 90]);
+    
+    assert_eq!(Backoff::Staggered.spins(1, 3, 3), 0);
+}
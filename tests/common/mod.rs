@@ -0,0 +1,15 @@
+// Not every test uses every helper
+#![allow(dead_code)]
+
+use std::thread;
+use spin_bitwise::RwLock;
+
+pub fn no_locks() -> &'static [&'static RwLock<u32>] {
+    &[]
+}
+
+pub fn wait_until<F: Fn() -> bool>(cond: F) {
+    while !cond() {
+        thread::yield_now();
+    }
+}
@@ -0,0 +1,51 @@
+extern crate spin_bitwise;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use spin_bitwise::{CountingRwLock, COUNTING_ARCH};
+
+#[test]
+fn full_slot_is_refused() {
+    let lock = CountingRwLock::new(0);
+    let guards: Vec<_> = (0..COUNTING_ARCH.slot_max).map(|_| lock.read(3)).collect();
+    
+    assert_eq!(lock.slot_count(3), COUNTING_ARCH.slot_max);
+    assert!(lock.try_read(3).is_none());
+    assert!(catch_unwind(AssertUnwindSafe(|| { lock.read(3); })).is_err());
+    assert_eq!(lock.slot_count(3), COUNTING_ARCH.slot_max);
+    assert_eq!(lock.slot_count(4), 0);
+    
+    drop(guards);
+    assert_eq!(lock.slot_count(3), 0);
+}
+
+#[test]
+fn racing_readers_never_carry_into_the_next_slot() {
+    let threads_cnt = 2 * COUNTING_ARCH.slot_max;
+    let lock = Arc::new(CountingRwLock::new(0));
+    let barrier = Arc::new(Barrier::new(threads_cnt));
+    
+    let threads: Vec<_> = (0..threads_cnt).map(|_| {
+        let (lock, barrier) = (lock.clone(), barrier.clone());
+        thread::spawn(move || {
+            barrier.wait();
+            
+            for _ in 0..1000 {
+                let guard = lock.try_read(0);
+                
+                assert!(lock.slot_count(0) <= COUNTING_ARCH.slot_max);
+                assert_eq!(lock.slot_count(1), 0);
+                
+                drop(guard);
+            }
+        })
+    }).collect();
+    
+    for t in threads {
+        t.join().unwrap();
+    }
+    
+    assert_eq!(lock.slot_count(0), 0);
+    *lock.write() += 1;
+}
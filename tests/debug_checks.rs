@@ -0,0 +1,35 @@
+#![cfg(feature = "debug_checks")]
+
+extern crate spin_bitwise;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use spin_bitwise::RwLock;
+
+#[test]
+fn self_deadlocks_are_detected() {
+    let lock = RwLock::new(0);
+    
+    let read = lock.read(0);
+    assert!(catch_unwind(AssertUnwindSafe(|| { lock.write(); })).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| { lock.read(0); })).is_err());
+    drop(lock.read(1));
+    drop(read);
+    
+    let write = lock.write();
+    assert!(catch_unwind(AssertUnwindSafe(|| { lock.read_any(); })).is_err());
+    drop(write);
+    
+    // Every record is gone again
+    *lock.write() += 1;
+}
+
+#[test]
+fn leaked_guards_stay_recorded_until_forced_unlock() {
+    let lock = RwLock::new(0);
+    
+    let _ = spin_bitwise::ReadLockGuard::leak(lock.read(2));
+    assert!(catch_unwind(AssertUnwindSafe(|| { lock.write(); })).is_err());
+    
+    unsafe { lock.force_unlock_read(2); }
+    *lock.write() += 1;
+}
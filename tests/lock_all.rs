@@ -0,0 +1,22 @@
+extern crate spin_bitwise;
+
+use spin_bitwise::*;
+
+#[test]
+fn mixed_types_lock_together() {
+    let (count, name) = (RwLock::new(1u32), RwLock::new(String::from("a")));
+    
+    {
+        let (count, mut name) = lock_all(0, (read_req(&count), write_req(&name))).unwrap();
+        name.push_str(&count.to_string());
+    }
+    
+    assert_eq!(*name.read(0), "a1");
+}
+
+#[test]
+fn overlapping_members_are_refused() {
+    let lock = RwLock::new(0u32);
+    
+    assert_eq!(lock_all(0, (read_req(&lock), write_req(&lock))).err(), Some(LockManyError::Overlap { read: 0, write: 1 }));
+}
@@ -0,0 +1,48 @@
+extern crate spin_bitwise;
+
+use std::collections::HashMap;
+use spin_bitwise::*;
+
+fn locks() -> HashMap<u32, RwLock<u32>> {
+    (0..4).map(|key| (key, RwLock::new(key * 10))).collect()
+}
+
+#[test]
+fn missing_key_is_reported_with_its_set_and_position() {
+    let map = locks();
+    
+    let err = lock_keys(0, &map, &[0, 1], &[2, 7]).err().unwrap();
+    assert_eq!(err, LockKeysError::Missing { key: 7, kind: LockKind::Write, index: 1 });
+    
+    let err = lock_keys(0, &map, &[0, 9, 1], &[]).err().unwrap();
+    assert_eq!(err, LockKeysError::Missing { key: 9, kind: LockKind::Read, index: 1 });
+    
+    // Nothing was locked on the way
+    assert!(map.values().all(|lock| lock.try_write().is_some()));
+}
+
+#[test]
+fn duplicate_key_is_refused() {
+    let map = locks();
+    
+    let err = lock_keys(0, &map, &[], &[3, 1, 3]).err().unwrap();
+    assert_eq!(err, LockKeysError::Lock(LockManyError::Duplicate { kind: LockKind::Write, first: 0, second: 2 }));
+    
+    let err = lock_keys(0, &map, &[2], &[0, 2]).err().unwrap();
+    assert_eq!(err, LockKeysError::Lock(LockManyError::Overlap { read: 0, write: 1 }));
+    
+    assert!(map.values().all(|lock| lock.try_write().is_some()));
+}
+
+#[test]
+fn guards_are_returned_by_key() {
+    let map = locks();
+    
+    {
+        let mut locked = lock_keys(0, &map, &[0, 1], &[3]).unwrap();
+        *locked.get_mut(&3).unwrap() += *locked.get(&1).unwrap();
+        assert_eq!(locked.get_mut(&0), None);
+    }
+    
+    assert_eq!(*map[&3].read(0), 40);
+}
@@ -0,0 +1,104 @@
+extern crate spin_bitwise;
+
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use spin_bitwise::*;
+use common::*;
+
+type Locks<'a> = &'a [&'a RwLock<u32>];
+
+#[test]
+fn reused_timeout_is_measured_from_each_call() {
+    let lock = RwLock::new(0u32);
+    let (held_tx, held_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    
+    thread::scope(|s| {
+        let lock = &lock;
+        
+        s.spawn(move || {
+            let _blocker = lock.write();
+            held_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        });
+        
+        held_rx.recv().unwrap();
+        
+        let options = LockManyOptions::new().timeout(Duration::from_millis(20));
+        thread::sleep(Duration::from_millis(30));
+        
+        for _ in 0..2 {
+            match RwLock::lock_many_with(0, no_locks(), &[lock], options) {
+                Err(LockManyError::GaveUp { retries, .. }) => assert!(retries > 0),
+                _ => panic!("expected the call to give up"),
+            }
+        }
+        
+        release_tx.send(()).unwrap();
+    });
+}
+
+#[test]
+fn overlap_and_duplicates_are_refused_with_positions() {
+    let locks: Vec<RwLock<u32>> = (0..4).map(RwLock::new).collect();
+    let (a, b, c, d) = (&locks[0], &locks[1], &locks[2], &locks[3]);
+    
+    let cases: [(Locks, Locks, LockManyError); 3] = [
+        (&[a, b], &[c, b], LockManyError::Overlap { read: 1, write: 1 }),
+        (&[d, a, c, a], &[b], LockManyError::Duplicate { kind: LockKind::Read, first: 1, second: 3 }),
+        (&[a], &[c, b, d, c], LockManyError::Duplicate { kind: LockKind::Write, first: 0, second: 3 }),
+    ];
+    
+    for &(read, write, expected) in cases.iter() {
+        assert_eq!(RwLock::lock_many(0, read, write).err(), Some(expected));
+        assert_eq!(RwLock::lock_many_ordered(0, read, write).err(), Some(expected));
+    }
+    
+    // Pairwise checks of the fixed size sets agree with the sorted ones
+    assert_eq!(RwLock::lock_many_array(0, [a, b], [c, b]).err(), Some(LockManyError::Overlap { read: 1, write: 1 }));
+    assert_eq!(RwLock::lock_many_array(0, [d, a, c, a], [b]).err(),
+               Some(LockManyError::Duplicate { kind: LockKind::Read, first: 1, second: 3 }));
+    assert_eq!(RwLock::lock_many_array(0, [a], [c, b, d, c]).err(),
+               Some(LockManyError::Duplicate { kind: LockKind::Write, first: 0, second: 3 }));
+    
+    // Refused before anything was locked
+    assert!(locks.iter().all(|lock| lock.try_write().is_some()));
+}
+
+#[test]
+fn lock_many_array_returns_guards_in_order() {
+    let locks: Vec<RwLock<u32>> = (0..5).map(RwLock::new).collect();
+    
+    {
+        let (read, mut write) = RwLock::lock_many_array(1, [&locks[3], &locks[0]], [&locks[4], &locks[1], &locks[2]]).unwrap();
+        
+        assert_eq!((*read[0], *read[1]), (3, 0));
+        assert_eq!(read[0].idx(), 1);
+        
+        for (guard, add) in write.iter_mut().zip([10, 20, 30].iter()) {
+            **guard += add;
+        }
+        
+        thread::scope(|s| {
+            let free = s.spawn(|| locks.iter().map(|lock| lock.try_read_any().is_some()).collect::<Vec<_>>());
+            assert_eq!(free.join().unwrap(), vec![true, false, false, true, false]);
+        });
+    }
+    
+    assert_eq!(locks.iter().map(|lock| *lock.read(0)).collect::<Vec<_>>(), vec![0, 21, 32, 3, 14]);
+}
+
+#[test]
+fn lock_many_array_refuses_a_write_set_with_a_duplicate() {
+    let (first, second) = (RwLock::new(0u32), RwLock::new(0u32));
+    
+    let err = RwLock::lock_many_array(0, [], [&first, &second, &first]).err().unwrap();
+    assert_eq!(err, LockManyError::Duplicate { kind: LockKind::Write, first: 0, second: 2 });
+    
+    // Nothing is left held behind
+    let (_, mut write) = RwLock::lock_many_array(0, [], [&first, &second]).unwrap();
+    *write[1] += 1;
+}
@@ -0,0 +1,120 @@
+extern crate spin_bitwise;
+
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use spin_bitwise::*;
+use common::*;
+
+#[test]
+fn released_members_shift_the_rest_down() {
+    let locks: Vec<RwLock<u32>> = (0..6).map(RwLock::new).collect();
+    let mut locked = RwLock::lock_many(0, &[&locks[0], &locks[1], &locks[2]], &[&locks[3], &locks[4], &locks[5]]).unwrap();
+    
+    locked.release_read(0);
+    assert_eq!(locked.read.iter().map(|r| **r).collect::<Vec<_>>(), vec![1, 2]);
+    
+    locked.release_write(1);
+    assert_eq!(locked.write.iter().map(|w| **w).collect::<Vec<_>>(), vec![3, 5]);
+    
+    // Released ones are free, the rest is still held
+    thread::scope(|s| {
+        let free = s.spawn(|| locks.iter().map(|lock| lock.try_write().is_some()).collect::<Vec<_>>());
+        assert_eq!(free.join().unwrap(), vec![true, false, false, false, true, false]);
+    });
+    
+    *locked.write[1] += 10;
+    drop(locked);
+    assert_eq!(*locks[5].read(0), 15);
+}
+
+#[test]
+fn downgraded_member_lets_readers_in() {
+    let (first, second) = (RwLock::new(1u32), RwLock::new(2u32));
+    let mut locked = RwLock::lock_many(0, no_locks(), &[&first, &second]).unwrap();
+    
+    *locked.write[0] += 10;
+    let r = locked.downgrade_write(0);
+    
+    assert_eq!((r, *locked.read[r]), (0, 11));
+    assert_eq!(*locked.write[0], 2);
+    
+    thread::scope(|s| {
+        assert_eq!(s.spawn(|| first.try_read_any().map(|guard| *guard)).join().unwrap(), Some(11));
+        assert!(s.spawn(|| first.try_write().is_none()).join().unwrap());
+    });
+}
+
+#[test]
+fn upgrade_fails_with_another_reader_and_keeps_the_read() {
+    let lock = RwLock::new(0u32);
+    let (held_tx, held_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    
+    thread::scope(|s| {
+        let lock = &lock;
+        
+        s.spawn(move || {
+            let _other = lock.read(1);
+            held_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        });
+        
+        held_rx.recv().unwrap();
+        
+        let mut locked = RwLock::lock_many(0, &[lock], no_locks()).unwrap();
+        assert_eq!(locked.try_upgrade_read(0), None);
+        
+        assert_eq!((locked.read.len(), locked.write.len()), (1, 0));
+        assert_eq!(locked.read[0].idx(), 0);
+        assert!(lock.state().reader_slots & 1 != 0);
+        
+        release_tx.send(()).unwrap();
+        wait_until(|| lock.state().reader_slots == 1);
+        
+        assert_eq!(locked.try_upgrade_read(0), Some(0));
+        assert_eq!((locked.read.len(), locked.write.len()), (0, 1));
+        *locked.write[0] += 1;
+    });
+    
+    assert_eq!(*lock.read(0), 1);
+}
+
+#[test]
+fn upgrade_fails_on_a_reserved_lock() {
+    let (first, second) = (RwLock::new(0u32), RwLock::new(0u32));
+    let (blocked_tx, blocked_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    
+    thread::scope(|s| {
+        let (first, second) = (&first, &second);
+        
+        // Keeps the reserving call below waiting
+        s.spawn(move || {
+            let _blocker = second.write();
+            blocked_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        });
+        
+        blocked_rx.recv().unwrap();
+        
+        let mut locked = RwLock::lock_many(0, &[first], no_locks()).unwrap();
+        
+        let reserver = s.spawn(move || {
+            let options = LockManyOptions::new().reserve_after(Some(0));
+            *RwLock::lock_many_with(1, no_locks(), &[first, second], options).unwrap().write[0] += 1;
+        });
+        
+        wait_until(|| first.state().reserved);
+        
+        assert_eq!(locked.try_upgrade_read(0), None);
+        assert_eq!((locked.read.len(), locked.write.len()), (1, 0));
+        
+        drop(locked);
+        release_tx.send(()).unwrap();
+        reserver.join().unwrap();
+    });
+    
+    assert_eq!(*first.read(0), 1);
+}
@@ -0,0 +1,61 @@
+extern crate spin_bitwise;
+
+use std::thread;
+use spin_bitwise::*;
+
+fn writable(locks: &[RwLock<u32>]) -> Vec<bool> {
+    thread::scope(|s| {
+        s.spawn(|| locks.iter().map(|lock| lock.try_write().is_some()).collect()).join().unwrap()
+    })
+}
+
+#[test]
+fn changes_invalidate_the_cached_check() {
+    let locks: Vec<RwLock<u32>> = (0..3).map(RwLock::new).collect();
+    let mut set = LockSet::new();
+    
+    set.add_read(&locks[0]).add_write(&locks[1]);
+    assert!(set.acquire(0).is_ok());
+    
+    set.add_write(&locks[0]);
+    assert_eq!(set.acquire(0).err(), Some(LockManyError::Overlap { read: 0, write: 1 }));
+    assert_eq!(set.acquire_ordered(0).err(), Some(LockManyError::Overlap { read: 0, write: 1 }));
+    
+    set.clear();
+    set.add_write(&locks[2]);
+    
+    {
+        let mut guard = set.acquire_ordered(0).unwrap();
+        assert_eq!((guard.read().len(), guard.write().len()), (0, 1));
+        *guard.write()[0] += 1;
+    }
+    
+    set.add_write(&locks[2]);
+    assert_eq!(set.acquire(0).err(), Some(LockManyError::Duplicate { kind: LockKind::Write, first: 0, second: 1 }));
+    
+    assert_eq!(writable(&locks), vec![true, true, true]);
+    assert_eq!(*locks[2].read(0), 3);
+}
+
+#[test]
+fn guards_are_released_on_drop() {
+    let locks: Vec<RwLock<u32>> = (0..4).map(RwLock::new).collect();
+    let mut set = LockSet::new();
+    
+    set.add_read(&locks[2]).add_write(&locks[0]).add_write(&locks[3]);
+    
+    for _ in 0..2 {
+        let mut guard = set.acquire(1).unwrap();
+        
+        {
+            let (read, write) = guard.split();
+            *write[1] += *read[0];
+        }
+        
+        assert_eq!(writable(&locks), vec![false, true, false, false]);
+        drop(guard);
+        assert_eq!(writable(&locks), vec![true, true, true, true]);
+    }
+    
+    assert_eq!(*locks[3].read(0), 7);
+}
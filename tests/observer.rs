@@ -0,0 +1,63 @@
+#![cfg(feature = "observer")]
+
+extern crate spin_bitwise;
+
+use std::sync::{Arc, Mutex};
+use spin_bitwise::*;
+
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl LockObserver for Recorder
+{
+    fn on_acquire_start(&self, _lock: usize, kind: LockKind, _idx: usize) {
+        self.0.lock().unwrap().push(format!("start {:?}", kind));
+    }
+    
+    fn on_acquired(&self, _lock: usize, kind: LockKind, _idx: usize, _spins: usize) {
+        self.0.lock().unwrap().push(format!("acquired {:?}", kind));
+    }
+    
+    fn on_release(&self, _lock: usize, kind: LockKind, _idx: usize) {
+        self.0.lock().unwrap().push(format!("release {:?}", kind));
+    }
+}
+
+fn take(log: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+    log.lock().unwrap().drain(..).collect()
+}
+
+#[test]
+fn global_observer_is_set_once() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    
+    assert!(set_global_observer(Recorder(log.clone())));
+    assert!(!set_global_observer(Recorder(log.clone())));
+}
+
+#[test]
+fn single_attempts_report_start() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let lock = RwLock::with_observer(0, Recorder(log.clone()));
+    
+    drop(lock.try_read_any().unwrap());
+    assert_eq!(take(&log), vec!["start Read", "acquired Read", "release Read"]);
+    
+    drop(RwLock::try_lock_many(0, &[] as &[&RwLock<i32>], &[&lock]).unwrap());
+    assert_eq!(take(&log), vec!["start Write", "acquired Write", "release Write"]);
+    
+    drop(lock.try_write().unwrap());
+    assert_eq!(take(&log), vec!["start Write", "acquired Write", "release Write"]);
+    
+    let held = lock.write();
+    assert!(lock.try_read_any().is_none());
+    assert_eq!(take(&log), vec!["start Write", "acquired Write", "start Read"]);
+    
+    drop(held);
+    
+    // Fails from another thread, `debug_checks` refuses it on the reading one
+    let held = lock.read(0);
+    assert!(std::thread::scope(|s| s.spawn(|| lock.try_write().is_none()).join().unwrap()));
+    assert_eq!(take(&log), vec!["release Write", "start Read", "acquired Read", "start Write"]);
+    
+    drop(held);
+}
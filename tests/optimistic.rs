@@ -0,0 +1,47 @@
+extern crate spin_bitwise;
+
+mod common;
+
+use spin_bitwise::*;
+use common::*;
+
+#[test]
+fn stamp_taken_while_write_locked_is_invalid() {
+    let lock = RwLock::new(0u32);
+    
+    let stamp = {
+        let _guard = lock.write();
+        lock.try_optimistic_read()
+    };
+    
+    assert!(!stamp.is_valid());
+    assert!(!lock.validate(stamp));
+    
+    // Readers do not get in the way
+    let _reader = lock.read(0);
+    let stamp = lock.try_optimistic_read();
+    assert!(stamp.is_valid() && lock.validate(stamp));
+}
+
+#[test]
+fn every_write_invalidates_the_stamp() {
+    let lock = RwLock::new(0u32);
+    
+    let writes: [&dyn Fn(); 5] = [
+        &|| *lock.write() += 1,
+        &|| *lock.try_write().unwrap() += 1,
+        &|| *RwLock::lock_many(0, no_locks(), &[&lock]).unwrap().write[0] += 1,
+        &|| *RwLock::lock_many_ordered(0, no_locks(), &[&lock]).unwrap().write[0] += 1,
+        &|| *ReadLockGuard::try_upgrade(lock.read(0)).ok().unwrap() += 1,
+    ];
+    
+    for write in writes.iter() {
+        let stamp = lock.try_optimistic_read();
+        assert!(lock.validate(stamp));
+        
+        write();
+        assert!(!lock.validate(stamp));
+    }
+    
+    assert_eq!(*lock.read(0), 5);
+}
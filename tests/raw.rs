@@ -0,0 +1,74 @@
+extern crate spin_bitwise;
+
+use spin_bitwise::RawBitwiseRwLock;
+
+static FD_LOCK: RawBitwiseRwLock = RawBitwiseRwLock::new();
+
+#[test]
+fn static_lock_guards_an_external_resource() {
+    FD_LOCK.lock_exclusive();
+    assert!(FD_LOCK.is_write_locked());
+    unsafe { FD_LOCK.unlock_exclusive(); }
+    
+    assert!(!FD_LOCK.is_locked());
+}
+
+#[test]
+fn single_attempts_fail_under_contention() {
+    let lock = RawBitwiseRwLock::default();
+    
+    lock.lock_shared(0);
+    assert!(!lock.try_lock_exclusive());
+    assert!(!lock.try_lock_shared(0));
+    assert!(lock.try_lock_shared(1));
+    assert_eq!(lock.reader_count(), 2);
+    
+    unsafe {
+        lock.unlock_shared(0);
+        lock.unlock_shared(1);
+    }
+    
+    assert!(lock.try_lock_exclusive());
+    assert!(!lock.try_lock_shared(2));
+    assert!(!lock.try_lock_exclusive());
+    
+    // A failed attempt leaves nothing behind
+    assert_eq!(lock.reader_count(), 0);
+    
+    unsafe { lock.unlock_exclusive(); }
+    assert!(!lock.is_locked());
+}
+
+#[test]
+fn upgrade_and_downgrade_round_trip() {
+    let lock = RawBitwiseRwLock::new();
+    
+    lock.lock_shared(3);
+    lock.lock_shared(4);
+    
+    unsafe {
+        // Another reader holds the lock
+        assert!(!lock.try_upgrade(3));
+        assert_eq!(lock.reader_count(), 2);
+        
+        lock.unlock_shared(4);
+        assert!(lock.try_upgrade(3));
+    }
+    
+    assert!(lock.is_write_locked());
+    assert_eq!(lock.reader_count(), 0);
+    
+    unsafe { lock.downgrade(3); }
+    
+    assert!(!lock.is_write_locked());
+    assert_eq!(lock.state().reader_slots, 1 << 3);
+    assert!(lock.try_lock_shared(5));
+    assert!(!lock.try_lock_exclusive());
+    
+    unsafe {
+        lock.unlock_shared(5);
+        lock.unlock_shared(3);
+    }
+    
+    assert!(!lock.is_locked());
+}
@@ -0,0 +1,21 @@
+extern crate spin_bitwise;
+
+use spin_bitwise::RwLock;
+
+#[test]
+fn read_any_reports_the_slot_it_took() {
+    let lock = RwLock::new(0);
+    
+    let first = lock.read(0);
+    let second = lock.read_any();
+    let third = lock.read_any();
+    
+    assert_eq!((first.idx(), second.idx(), third.idx()), (0, 1, 2));
+    
+    drop(second);
+    assert_eq!(lock.state().reader_slots, 0b101);
+    assert_eq!(lock.read_any().idx(), 1);
+    
+    drop((first, third));
+    assert!(!lock.state().is_locked());
+}
@@ -0,0 +1,84 @@
+extern crate spin_bitwise;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+use spin_bitwise::ReentrantRwLock;
+
+#[test]
+fn owner_nests_read_and_write() {
+    let lock = ReentrantRwLock::new(vec![1]);
+    
+    let mut outer = lock.write();
+    outer.borrow_mut().push(2);
+    
+    {
+        let read = lock.read(0);
+        let nested = lock.write();
+        
+        assert!(nested.is_nested());
+        assert_eq!(*read, vec![1, 2]);
+        assert_eq!(*nested, vec![1, 2]);
+    }
+    
+    outer.borrow_mut().push(3);
+    drop(outer);
+    
+    assert!(!lock.is_owned_by_current_thread());
+    assert_eq!(*lock.read(0), vec![1, 2, 3]);
+}
+
+#[test]
+fn nested_acquisition_during_borrow_mut_panics() {
+    let lock = ReentrantRwLock::new(vec![1]);
+    let mut outer = lock.write();
+    
+    {
+        let mut data = outer.borrow_mut();
+        
+        assert!(catch_unwind(AssertUnwindSafe(|| { lock.read(0); })).is_err());
+        assert!(catch_unwind(AssertUnwindSafe(|| { lock.write(); })).is_err());
+        
+        data.push(2);
+    }
+    
+    // The borrow has ended, nesting works again
+    assert_eq!(*lock.read(0), vec![1, 2]);
+}
+
+#[test]
+fn borrow_mut_with_nested_guards_panics() {
+    let lock = ReentrantRwLock::new(0);
+    let mut outer = lock.write();
+    let nested = lock.read(0);
+    
+    assert!(catch_unwind(AssertUnwindSafe(|| { outer.borrow_mut(); })).is_err());
+    
+    drop(nested);
+    *outer.borrow_mut() += 1;
+    
+    let mut inner = lock.write();
+    assert!(catch_unwind(AssertUnwindSafe(|| { inner.borrow_mut(); })).is_err());
+}
+
+#[test]
+fn other_threads_wait_for_the_outermost_guard() {
+    let lock = Arc::new(ReentrantRwLock::new(0u64));
+    
+    let threads: Vec<_> = (0..2).map(|_| {
+        let lock = lock.clone();
+        thread::spawn(move || {
+            for _ in 0..1000 {
+                let mut outer = lock.write();
+                let value = *lock.read(0);
+                *outer.borrow_mut() = value + 1;
+            }
+        })
+    }).collect();
+    
+    for t in threads {
+        t.join().unwrap();
+    }
+    
+    assert_eq!(*lock.read(0), 2000);
+}
@@ -0,0 +1,183 @@
+extern crate spin_bitwise;
+
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use spin_bitwise::*;
+use common::*;
+
+#[test]
+fn holding_one_lock_while_locking_another_does_not_deadlock_by_default() {
+    let locks = Arc::new((RwLock::new(0u32), RwLock::new(0u32)));
+    let (held_tx, held_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+    
+    // Holds a read on the second lock, then asks for the first one
+    let holder = {
+        let locks = locks.clone();
+        thread::spawn(move || {
+            let _second = locks.1.read(1);
+            held_tx.send(()).unwrap();
+            
+            // Let the other call retry past any reservation threshold
+            thread::sleep(Duration::from_millis(50));
+            *RwLock::lock_many(1, no_locks(), &[&locks.0]).unwrap().write[0] += 1;
+        })
+    };
+    
+    held_rx.recv().unwrap();
+    
+    let both = {
+        let locks = locks.clone();
+        thread::spawn(move || {
+            let mut guards = RwLock::lock_many(0, no_locks(), &[&locks.0, &locks.1]).unwrap();
+            *guards.write[1] += 1;
+            done_tx.send(()).unwrap();
+        })
+    };
+    
+    assert!(done_rx.recv_timeout(Duration::from_secs(10)).is_ok(), "lock_many calls deadlocked");
+    
+    holder.join().unwrap();
+    both.join().unwrap();
+    
+    assert!(!locks.0.state().reserved && !locks.1.state().reserved);
+    assert_eq!((*locks.0.read(0), *locks.1.read(0)), (1, 1));
+}
+
+#[test]
+fn reservation_turns_away_multi_lock_calls_and_is_handed_back() {
+    let locks = Arc::new((RwLock::new(0u32), RwLock::new(0u32)));
+    let blocker = locks.1.write();
+    
+    let reserver = {
+        let locks = locks.clone();
+        thread::spawn(move || {
+            let options = LockManyOptions::new().reserve_after(Some(0));
+            let mut guards = RwLock::lock_many_with(0, no_locks(), &[&locks.0, &locks.1], options).unwrap();
+            *guards.write[0] += 1;
+        })
+    };
+    
+    wait_until(|| locks.0.state().reserved && locks.1.state().reserved);
+    
+    // Free, but reserved for the other call
+    let contended = RwLock::try_lock_many(1, no_locks(), &[&locks.0]).err().unwrap();
+    assert_eq!((contended.index, contended.kind), (0, LockKind::Write));
+    
+    let options = LockManyOptions::new().max_retries(3);
+    assert!(RwLock::lock_many_with(1, no_locks(), &[&locks.0], options).is_err());
+    
+    // Single lock calls ignore reservations
+    drop(locks.0.write());
+    
+    drop(blocker);
+    reserver.join().unwrap();
+    
+    assert!(!locks.0.state().reserved && !locks.1.state().reserved);
+    assert_eq!(*RwLock::lock_many(1, no_locks(), &[&locks.0]).unwrap().write[0], 1);
+}
+
+#[test]
+fn giving_up_releases_the_reservation() {
+    let (first, second) = (RwLock::new(0u32), RwLock::new(0u32));
+    let (held_tx, held_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    
+    thread::scope(|s| {
+        // Blocks from another thread, taking a held lock again trips `debug_checks`
+        let (first, second) = (&first, &second);
+        
+        s.spawn(move || {
+            let _blocker = second.write();
+            held_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        });
+        
+        held_rx.recv().unwrap();
+        
+        let options = LockManyOptions::new().reserve_after(Some(0)).max_retries(3);
+        
+        match RwLock::lock_many_with(0, no_locks(), &[first, second], options) {
+            Err(LockManyError::GaveUp { retries, .. }) => assert_eq!(retries, 3),
+            _ => panic!("expected the call to give up"),
+        }
+        
+        assert!(!first.state().reserved && !second.state().reserved);
+        
+        release_tx.send(()).unwrap();
+    });
+    
+    assert!(RwLock::try_lock_many(0, no_locks(), &[&first, &second]).is_ok());
+}
+
+#[cfg(feature = "observer")]
+#[test]
+fn panicking_observer_releases_the_reservation() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    
+    struct Panicking;
+    
+    impl LockObserver for Panicking
+    {
+        fn on_acquired(&self, _lock: usize, _kind: LockKind, _idx: usize, _spins: usize) {
+            panic!("observer failed");
+        }
+    }
+    
+    let lock = RwLock::with_observer(0u32, Panicking);
+    let options = LockManyOptions::new().reserve_after(Some(0));
+    
+    assert!(catch_unwind(AssertUnwindSafe(|| {
+        RwLock::lock_many_with(0, no_locks(), &[&lock], options).ok();
+    })).is_err());
+    
+    assert!(!lock.state().reserved);
+}
+
+#[test]
+fn large_set_is_not_starved_by_small_sets() {
+    const LOCKS_CNT: usize = 8;
+    
+    let locks: Arc<Vec<RwLock<u32>>> = Arc::new((0..LOCKS_CNT).map(|_| RwLock::new(0)).collect());
+    let done = Arc::new(AtomicBool::new(false));
+    
+    // Keep some pair of the locks held at almost every moment
+    let small: Vec<_> = (0..2).map(|t| {
+        let (locks, done) = (locks.clone(), done.clone());
+        thread::spawn(move || {
+            let mut i = t;
+            
+            while !done.load(Ordering::Relaxed) {
+                let pair = [&locks[i % LOCKS_CNT], &locks[(i + 1) % LOCKS_CNT]];
+                let mut guards = RwLock::lock_many(t + 1, no_locks(), &pair).unwrap();
+                *guards.write[0] += 1;
+                
+                i += 2;
+            }
+        })
+    }).collect();
+    
+    let options = LockManyOptions::new().reserve_after(Some(RESERVE_AFTER));
+    
+    for _ in 0..20 {
+        let all: Vec<_> = locks.iter().collect();
+        let mut guards = RwLock::lock_many_with(0, no_locks(), &all, options).unwrap();
+        
+        for guard in guards.write.iter_mut() {
+            **guard += 1;
+        }
+    }
+    
+    done.store(true, Ordering::Relaxed);
+    
+    for t in small {
+        t.join().unwrap();
+    }
+    
+    assert!(locks.iter().all(|lock| !lock.state().reserved));
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "stats")]
+
+extern crate spin_bitwise;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use spin_bitwise::*;
+
+#[test]
+fn acquisitions_and_contention_are_counted() {
+    let lock = RwLock::new(0u32);
+    
+    drop(lock.read(0));
+    *lock.write() += 1;
+    
+    let stats = lock.stats().snapshot();
+    assert_eq!((stats.acquisitions, stats.contended, stats.spins), (2, 0, 0));
+    
+    thread::scope(|s| {
+        let read = lock.read(0);
+        let writer = s.spawn(|| *lock.write() += 1);
+        
+        thread::sleep(Duration::from_millis(20));
+        drop(read);
+        writer.join().unwrap();
+    });
+    
+    let stats = lock.stats().reset();
+    assert_eq!((stats.acquisitions, stats.contended), (4, 1));
+    assert!(stats.spins > 0);
+    
+    assert_eq!(lock.stats().snapshot(), LockStatsSnapshot::default());
+}
+
+#[test]
+fn lock_many_retries_are_blamed_on_the_busy_lock_only() {
+    let (free, busy) = (RwLock::new(0u32), RwLock::new(0u32));
+    let (held_tx, held_rx) = mpsc::channel();
+    
+    thread::scope(|s| {
+        let busy = &busy;
+        
+        s.spawn(move || {
+            let _blocker = busy.write();
+            held_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        });
+        
+        held_rx.recv().unwrap();
+        drop(RwLock::lock_many(0, &[&free], &[busy]).unwrap());
+    });
+    
+    let (free, busy) = (free.stats().snapshot(), busy.stats().snapshot());
+    
+    assert_eq!((free.acquisitions, free.contended, free.spins, free.lock_many_retries), (1, 0, 0, 0));
+    assert_eq!(busy.acquisitions, 2);
+    assert!(busy.lock_many_retries > 0);
+}